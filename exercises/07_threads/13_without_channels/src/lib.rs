@@ -4,4 +4,6 @@
 //  Fix the `todo!()` in the testing code and see how the new design can be used.
 
 pub mod data;
+pub mod sharded;
 pub mod store;
+mod sync;
@@ -0,0 +1,114 @@
+// A `TicketStore` that can be shared as-is (e.g. behind an `Arc`), without an outer lock.
+//
+// Tickets are spread across shards according to the hash of their id, and each shard
+// has its own lock: operations on tickets that live in different shards never contend.
+// Ids are allocated with an atomic counter, so inserting doesn't require a global lock either.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use crate::data::{Status, Ticket, TicketDraft};
+use crate::store::TicketId;
+use crate::sync::{AtomicU64, Ordering, RwLock};
+
+type Shard = RwLock<BTreeMap<TicketId, Ticket>>;
+
+pub struct ShardedTicketStore {
+    shards: Box<[Shard]>,
+    counter: AtomicU64,
+}
+
+impl ShardedTicketStore {
+    pub fn new() -> Self {
+        let parallelism = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(parallelism * 4)
+    }
+
+    pub fn with_shards(n_shards: usize) -> Self {
+        assert!(n_shards > 0, "A store needs at least one shard");
+        Self {
+            shards: (0..n_shards).map(|_| Shard::new(BTreeMap::new())).collect(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add_ticket(&self, ticket: TicketDraft) -> TicketId {
+        let id = TicketId(self.counter.fetch_add(1, Ordering::Relaxed));
+        let ticket = Ticket {
+            id,
+            title: ticket.title,
+            description: ticket.description,
+            status: Status::ToDo,
+        };
+        self.shard(id).write().unwrap().insert(id, ticket);
+        id
+    }
+
+    // Tickets are handed out by value: mutations must go through `update`,
+    // otherwise `snapshot` couldn't guarantee a consistent view.
+    pub fn get(&self, id: TicketId) -> Option<Ticket> {
+        self.shard(id).read().unwrap().get(&id).cloned()
+    }
+
+    // Runs `f` on the ticket, while holding the lock on its shard.
+    // `f` can change anything but the id: tickets are found by id, so it panics if it tries.
+    pub fn update<F, R>(&self, id: TicketId, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Ticket) -> R,
+    {
+        let mut shard = self.shard(id).write().unwrap();
+        let ticket = shard.get_mut(&id)?;
+        let result = f(ticket);
+        if ticket.id != id {
+            // Put it back before panicking, so that the shard is left as it should be.
+            ticket.id = id;
+            drop(shard);
+            panic!("`update` can't change the id of ticket {id:?}");
+        }
+        Some(result)
+    }
+
+    pub fn remove(&self, id: TicketId) -> Option<Ticket> {
+        self.shard(id).write().unwrap().remove(&id)
+    }
+
+    // A point-in-time copy of every ticket, ordered by id.
+    // We hold a read lock on every shard while copying, so no write can sneak in halfway through.
+    pub fn snapshot(&self) -> Vec<Ticket> {
+        // Shards are always locked in the same order, so two snapshots can't deadlock.
+        let guards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.read().unwrap())
+            .collect();
+        let mut tickets: Vec<Ticket> = guards
+            .iter()
+            .flat_map(|shard| shard.values().cloned())
+            .collect();
+        tickets.sort_by_key(|ticket| ticket.id);
+        tickets
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard(&self, id: TicketId) -> &Shard {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+impl Default for ShardedTicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
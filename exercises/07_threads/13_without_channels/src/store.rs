@@ -1,10 +1,10 @@
 use std::collections::BTreeMap;
-use std::sync::{Arc, RwLock};
 
 use crate::data::{Status, Ticket, TicketDraft};
+use crate::sync::{Arc, RwLock};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct TicketId(u64);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketId(pub(crate) u64);
 
 #[derive(Clone)]
 pub struct TicketStore {
@@ -38,3 +38,9 @@ impl TicketStore {
         self.tickets.get(&id).cloned()
     }
 }
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,14 @@
+// The primitives the stores are built on.
+//
+// Building with `RUSTFLAGS="--cfg loom"` swaps them for loom's, so that the model tests
+// in `tests/loom.rs` can explore every interleaving of the threads sharing a store.
+#[cfg(loom)]
+pub(crate) use loom::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
@@ -0,0 +1,93 @@
+// Model tests: run them with
+// `RUSTFLAGS="--cfg loom" cargo test -p without_channels --test loom --release`.
+#![cfg(loom)]
+use loom::sync::Arc;
+use loom::thread;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::TicketTitle;
+use without_channels::data::{Status, TicketDraft};
+use without_channels::sharded::ShardedTicketStore;
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+fn renamed() -> TicketTitle {
+    "Renamed".try_into().unwrap()
+}
+
+// The sharded store is shared as-is: there's no outer lock to serialize the threads.
+
+#[test]
+fn concurrent_inserts_never_share_an_id() {
+    loom::model(|| {
+        let store = Arc::new(ShardedTicketStore::with_shards(2));
+        let inserts: Vec<_> = (0..2)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || store.add_ticket(draft()))
+            })
+            .collect();
+        // Snapshots only ever see whole inserts.
+        let snapshot = store.snapshot();
+        let ids: Vec<_> = inserts.into_iter().map(|t| t.join().unwrap()).collect();
+
+        assert_ne!(ids[0], ids[1]);
+        for id in &ids {
+            assert!(store.get(*id).is_some());
+        }
+        assert!(snapshot.len() <= 2);
+        assert!(snapshot.iter().all(|ticket| ids.contains(&ticket.id)));
+    });
+}
+
+#[test]
+fn sharded_store_never_duplicates_ids_or_loses_updates() {
+    loom::model(|| {
+        let store = Arc::new(ShardedTicketStore::with_shards(2));
+        let existing = store.add_ticket(draft());
+
+        let inserts: Vec<_> = (0..2)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || store.add_ticket(draft()))
+            })
+            .collect();
+        let renamer = {
+            let store = store.clone();
+            thread::spawn(move || store.update(existing, |t| t.title = renamed()))
+        };
+        store.update(existing, |t| t.status = Status::Done);
+        let ids: Vec<_> = inserts.into_iter().map(|t| t.join().unwrap()).collect();
+        renamer.join().unwrap();
+
+        assert_ne!(ids[0], ids[1]);
+        assert!(!ids.contains(&existing));
+        assert_eq!(store.len(), 3);
+        let ticket = store.get(existing).unwrap();
+        assert_eq!(ticket.title, renamed());
+        assert_eq!(ticket.status, Status::Done);
+    });
+}
+
+#[test]
+fn updates_racing_a_removal_are_either_kept_or_reported() {
+    loom::model(|| {
+        let store = Arc::new(ShardedTicketStore::with_shards(2));
+        let id = store.add_ticket(draft());
+
+        let remover = {
+            let store = store.clone();
+            thread::spawn(move || store.remove(id))
+        };
+        let updated = store.update(id, |t| t.status = Status::Done).is_some();
+        let removed = remover.join().unwrap().unwrap();
+
+        // An update that went through is never lost with the ticket it applied to.
+        assert_eq!(removed.status == Status::Done, updated);
+        assert!(store.get(id).is_none());
+    });
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::spawn;
+
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use without_channels::data::{Status, TicketDraft};
+use without_channels::sharded::ShardedTicketStore;
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+#[test]
+fn concurrent_inserts_without_an_outer_lock() {
+    let store = Arc::new(ShardedTicketStore::with_shards(8));
+
+    let clients: Vec<_> = (0..4)
+        .map(|_| {
+            let store = store.clone();
+            spawn(move || {
+                (0..250)
+                    .map(|_| store.add_ticket(draft()))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let ids: Vec<_> = clients
+        .into_iter()
+        .flat_map(|client| client.join().unwrap())
+        .collect();
+
+    let unique: BTreeSet<_> = ids.iter().copied().collect();
+    assert_eq!(unique.len(), 1000);
+    assert_eq!(store.len(), 1000);
+
+    let snapshot = store.snapshot();
+    let snapshot_ids: Vec<_> = snapshot.iter().map(|ticket| ticket.id).collect();
+    assert_eq!(snapshot_ids, unique.into_iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn updates_and_removals() {
+    let store = ShardedTicketStore::with_shards(2);
+    let id = store.add_ticket(draft());
+
+    let previous = store.update(id, |ticket| {
+        std::mem::replace(&mut ticket.status, Status::InProgress)
+    });
+    assert_eq!(previous, Some(Status::ToDo));
+    assert_eq!(store.get(id).unwrap().status, Status::InProgress);
+
+    assert_eq!(store.remove(id).unwrap().id, id);
+    assert!(store.get(id).is_none());
+    assert!(store.update(id, |_| ()).is_none());
+    assert!(store.is_empty());
+}
+
+#[test]
+fn updates_cannot_change_the_id() {
+    let store = ShardedTicketStore::with_shards(2);
+    let id = store.add_ticket(draft());
+    let other = store.add_ticket(draft());
+
+    let attempt = catch_unwind(AssertUnwindSafe(|| {
+        store.update(id, |ticket| ticket.id = other);
+    }));
+    assert!(attempt.is_err());
+    // The ticket is still where it belongs, and its shard isn't poisoned.
+    assert_eq!(store.get(id).unwrap().id, id);
+    assert_eq!(store.get(other).unwrap().id, other);
+    assert_eq!(store.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "at least one shard")]
+fn a_store_needs_at_least_one_shard() {
+    ShardedTicketStore::with_shards(0);
+}
+
+#[test]
+fn missing_tickets_are_left_alone() {
+    let store = ShardedTicketStore::with_shards(4);
+    let kept = store.add_ticket(draft());
+    let removed = store.add_ticket(draft());
+    store.remove(removed).unwrap();
+
+    assert!(store.get(removed).is_none());
+    assert!(store.remove(removed).is_none());
+    // The closure never runs for a ticket that isn't there.
+    assert!(store
+        .update(removed, |_| panic!("No ticket to update"))
+        .is_none());
+    // Ids aren't reused after a removal.
+    let added = store.add_ticket(draft());
+    assert!(added != removed && added != kept);
+    let ids: Vec<_> = store.snapshot().iter().map(|ticket| ticket.id).collect();
+    assert_eq!(ids, vec![kept, added]);
+}
+
+#[test]
+fn a_panicking_update_only_poisons_its_own_shard() {
+    let store = Arc::new(ShardedTicketStore::with_shards(2));
+    let ids: Vec<_> = (0..16).map(|_| store.add_ticket(draft())).collect();
+
+    let poisoned = ids[0];
+    let handle = {
+        let store = store.clone();
+        spawn(move || store.update(poisoned, |_| panic!("Poisoning the shard")))
+    };
+    assert!(handle.join().is_err());
+
+    let readable: Vec<_> = ids
+        .iter()
+        .filter(|id| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| store.get(**id))).is_ok()
+        })
+        .collect();
+    assert!(!readable.contains(&&poisoned));
+    // Tickets in the other shard are still served.
+    assert!(!readable.is_empty());
+    for id in readable {
+        assert_eq!(store.get(*id).unwrap().status, Status::ToDo);
+    }
+}
@@ -0,0 +1,134 @@
+// The ticket store as an actor, on tokio.
+//
+// Same design as the thread-based server from `07_threads`, but with `tokio::sync::mpsc` and
+// `oneshot` channels: clients `.await` the response instead of blocking on it, so they never
+// hold up a runtime worker. The store runs on its own task, which stops once every client
+// has been dropped.
+use tokio::sync::{mpsc, oneshot};
+
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::store::{TicketId, TicketStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ClientError {
+    #[error("The ticket store is not running")]
+    Disconnected,
+    // The store went away with the command still queued, e.g. because its runtime was shut down.
+    #[error("The ticket store dropped the request without responding")]
+    RequestDropped,
+}
+
+// Spawns the store on the current runtime, with room for `capacity` queued commands.
+pub fn launch(capacity: usize) -> TicketStoreClient {
+    let (sender, receiver) = mpsc::channel(capacity);
+    tokio::spawn(server(receiver));
+    TicketStoreClient { sender }
+}
+
+#[derive(Clone)]
+pub struct TicketStoreClient {
+    sender: mpsc::Sender<Command>,
+}
+
+impl TicketStoreClient {
+    // Returns the new ticket.
+    pub async fn insert(&self, draft: TicketDraft) -> Result<Ticket, ClientError> {
+        self.request(|response_channel| Command::Insert {
+            draft,
+            response_channel,
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: TicketId) -> Result<Option<Ticket>, ClientError> {
+        self.request(|response_channel| Command::Get {
+            id,
+            response_channel,
+        })
+        .await
+    }
+
+    // Returns the updated ticket, or `None` if there's no ticket with that id.
+    pub async fn patch(
+        &self,
+        id: TicketId,
+        patch: TicketPatch,
+    ) -> Result<Option<Ticket>, ClientError> {
+        self.request(|response_channel| Command::Patch {
+            id,
+            patch,
+            response_channel,
+        })
+        .await
+    }
+
+    // Every ticket, ordered by id.
+    pub async fn list(&self) -> Result<Vec<Ticket>, ClientError> {
+        self.request(|response_channel| Command::List { response_channel })
+            .await
+    }
+
+    // Waits for room in the queue if it's full.
+    async fn request<T, F>(&self, command: F) -> Result<T, ClientError>
+    where
+        F: FnOnce(oneshot::Sender<T>) -> Command,
+    {
+        let (response_channel, response) = oneshot::channel();
+        self.sender
+            .send(command(response_channel))
+            .await
+            .map_err(|_| ClientError::Disconnected)?;
+        response.await.map_err(|_| ClientError::RequestDropped)
+    }
+}
+
+enum Command {
+    Insert {
+        draft: TicketDraft,
+        response_channel: oneshot::Sender<Ticket>,
+    },
+    Get {
+        id: TicketId,
+        response_channel: oneshot::Sender<Option<Ticket>>,
+    },
+    Patch {
+        id: TicketId,
+        patch: TicketPatch,
+        response_channel: oneshot::Sender<Option<Ticket>>,
+    },
+    List {
+        response_channel: oneshot::Sender<Vec<Ticket>>,
+    },
+}
+
+async fn server(mut receiver: mpsc::Receiver<Command>) {
+    let mut store = TicketStore::new();
+    // If a client stopped waiting for its response, there's no one to send it to:
+    // the send errors are ignored.
+    while let Some(command) = receiver.recv().await {
+        match command {
+            Command::Insert {
+                draft,
+                response_channel,
+            } => {
+                let _ = response_channel.send(store.add_ticket(draft).clone());
+            }
+            Command::Get {
+                id,
+                response_channel,
+            } => {
+                let _ = response_channel.send(store.get(id).cloned());
+            }
+            Command::Patch {
+                id,
+                patch,
+                response_channel,
+            } => {
+                let _ = response_channel.send(store.update(id, patch).cloned());
+            }
+            Command::List { response_channel } => {
+                let _ = response_channel.send(store.list().cloned().collect());
+            }
+        }
+    }
+}
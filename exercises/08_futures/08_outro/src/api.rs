@@ -0,0 +1,188 @@
+// The HTTP API:
+//  - `POST /tickets` creates a ticket
+//  - `GET /tickets` lists every ticket
+//  - `GET /tickets/{id}` retrieves a ticket
+//  - `PATCH /tickets/{id}` changes some of a ticket's fields
+//
+// Bodies are JSON. Invalid fields are reported all at once, with a 422 response:
+// `{"errors": {"title": "The title cannot be empty"}}`.
+// So are bodies that can't be read at all: `{"errors": {"body": "..."}}`.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ticket_fields::{TicketDescription, TicketTitle};
+use tokio::net::TcpListener;
+
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::shared::SharedStore;
+use crate::store::TicketId;
+
+pub fn router(store: SharedStore) -> Router {
+    Router::new()
+        .route("/tickets", get(list).post(create))
+        .route("/tickets/{id}", get(retrieve).patch(patch))
+        .with_state(store)
+}
+
+// Serves the API until the task is cancelled.
+pub async fn serve(listener: TcpListener, store: SharedStore) -> io::Result<()> {
+    tracing::info!(address = %listener.local_addr()?, "Serving the HTTP API");
+    axum::serve(listener, router(store)).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DraftBody {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PatchBody {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TicketBody {
+    pub id: u64,
+    pub title: String,
+    pub description: String,
+    pub status: Status,
+}
+
+impl From<&Ticket> for TicketBody {
+    fn from(ticket: &Ticket) -> Self {
+        Self {
+            id: ticket.id.0,
+            title: ticket.title.as_str().to_owned(),
+            description: ticket.description.as_str().to_owned(),
+            status: ticket.status,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound(TicketId),
+    // What's wrong with each field.
+    Invalid(BTreeMap<&'static str, String>),
+}
+
+// Malformed JSON, wrongly typed fields, a missing content type...
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::Invalid(BTreeMap::from([("body", rejection.body_text())]))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::NotFound(id) => (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": format!("There is no ticket with id {id}") })),
+            )
+                .into_response(),
+            ApiError::Invalid(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({ "errors": errors })),
+            )
+                .into_response(),
+        }
+    }
+}
+
+// Collects validation errors, so that they can all be reported at once.
+#[derive(Default)]
+struct FieldErrors(BTreeMap<&'static str, String>);
+
+impl FieldErrors {
+    fn required<T>(&mut self, field: &'static str, value: Option<T>) -> Option<T> {
+        if value.is_none() {
+            self.0.insert(field, format!("The {field} is required"));
+        }
+        value
+    }
+
+    fn check<T, E: Display>(&mut self, field: &'static str, value: Result<T, E>) -> Option<T> {
+        value.map_err(|e| self.0.insert(field, e.to_string())).ok()
+    }
+
+    fn finish(self) -> Result<(), ApiError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Invalid(self.0))
+        }
+    }
+}
+
+async fn create(
+    State(store): State<SharedStore>,
+    body: Result<Json<DraftBody>, JsonRejection>,
+) -> Result<(StatusCode, Json<TicketBody>), ApiError> {
+    let Json(body) = body?;
+    let mut errors = FieldErrors::default();
+    let title = errors
+        .required("title", body.title)
+        .and_then(|title| errors.check("title", TicketTitle::try_from(title)));
+    let description = errors
+        .required("description", body.description)
+        .and_then(|description| {
+            errors.check("description", TicketDescription::try_from(description))
+        });
+    errors.finish()?;
+    let draft = TicketDraft {
+        title: title.unwrap(),
+        description: description.unwrap(),
+    };
+    let ticket = store.insert(draft).await;
+    Ok((StatusCode::CREATED, Json(TicketBody::from(&ticket))))
+}
+
+async fn list(State(store): State<SharedStore>) -> Json<Vec<TicketBody>> {
+    Json(store.list().await.iter().map(TicketBody::from).collect())
+}
+
+async fn retrieve(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+) -> Result<Json<TicketBody>, ApiError> {
+    let id = TicketId(id);
+    let ticket = store.get(id).await.ok_or(ApiError::NotFound(id))?;
+    Ok(Json(TicketBody::from(&ticket)))
+}
+
+async fn patch(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+    body: Result<Json<PatchBody>, JsonRejection>,
+) -> Result<Json<TicketBody>, ApiError> {
+    let Json(body) = body?;
+    let id = TicketId(id);
+    let mut errors = FieldErrors::default();
+    let patch = TicketPatch {
+        title: body
+            .title
+            .and_then(|title| errors.check("title", TicketTitle::try_from(title))),
+        description: body.description.and_then(|description| {
+            errors.check("description", TicketDescription::try_from(description))
+        }),
+        status: body
+            .status
+            .and_then(|status| errors.check("status", status.parse())),
+    };
+    errors.finish()?;
+    let ticket = store.patch(id, patch).await.ok_or(ApiError::NotFound(id))?;
+    Ok(Json(TicketBody::from(&ticket)))
+}
@@ -0,0 +1,63 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use crate::store::TicketId;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ticket {
+    pub id: TicketId,
+    pub title: TicketTitle,
+    pub description: TicketDescription,
+    pub status: Status,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TicketDraft {
+    pub title: TicketTitle,
+    pub description: TicketDescription,
+}
+
+// Fields left to `None` are not changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TicketPatch {
+    pub title: Option<TicketTitle>,
+    pub description: Option<TicketDescription>,
+    pub status: Option<Status>,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    ToDo,
+    InProgress,
+    Done,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{0}` is not a status: expected one of `to_do`, `in_progress` or `done`")]
+pub struct UnknownStatus(String);
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::ToDo => "to_do",
+            Status::InProgress => "in_progress",
+            Status::Done => "done",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = UnknownStatus;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "to_do" => Ok(Status::ToDo),
+            "in_progress" => Ok(Status::InProgress),
+            "done" => Ok(Status::Done),
+            _ => Err(UnknownStatus(s.to_owned())),
+        }
+    }
+}
@@ -8,3 +8,10 @@
 //
 // Use Rust's package registry, crates.io, to find the dependencies you need
 // (if any) to build this system.
+
+pub mod actor;
+pub mod api;
+pub mod data;
+pub mod shared;
+pub mod store;
+pub mod text;
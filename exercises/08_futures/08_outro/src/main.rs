@@ -0,0 +1,20 @@
+// Serves the HTTP API on `TICKETS_ADDRESS` (`127.0.0.1:3000` by default)
+// and the text protocol on `TICKETS_TEXT_ADDRESS` (`127.0.0.1:3001` by default).
+// Both share the same store.
+use outro_08::shared::SharedStore;
+use outro_08::{api, text};
+use tokio::net::TcpListener;
+
+fn address(variable: &str, default: &str) -> String {
+    std::env::var(variable).unwrap_or_else(|_| default.into())
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    tracing_subscriber::fmt::init();
+    let store = SharedStore::default();
+    let http = TcpListener::bind(address("TICKETS_ADDRESS", "127.0.0.1:3000")).await?;
+    let text = TcpListener::bind(address("TICKETS_TEXT_ADDRESS", "127.0.0.1:3001")).await?;
+    tokio::try_join!(api::serve(http, store.clone()), text::serve(text, store))?;
+    Ok(())
+}
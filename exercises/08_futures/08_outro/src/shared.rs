@@ -0,0 +1,41 @@
+// A store that async tasks share directly, behind a `tokio::sync::RwLock`.
+//
+// Waiting for the lock yields to the runtime instead of blocking the worker thread,
+// and the lock is never held across an `.await`: tickets are handed out by value.
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::store::{TicketId, TicketStore};
+
+// Clones share the same store.
+#[derive(Clone, Default)]
+pub struct SharedStore {
+    store: Arc<RwLock<TicketStore>>,
+}
+
+impl SharedStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the new ticket.
+    pub async fn insert(&self, draft: TicketDraft) -> Ticket {
+        self.store.write().await.add_ticket(draft).clone()
+    }
+
+    pub async fn get(&self, id: TicketId) -> Option<Ticket> {
+        self.store.read().await.get(id).cloned()
+    }
+
+    // Returns the updated ticket, or `None` if there's no ticket with that id.
+    pub async fn patch(&self, id: TicketId, patch: TicketPatch) -> Option<Ticket> {
+        self.store.write().await.update(id, patch).cloned()
+    }
+
+    // Every ticket, ordered by id.
+    pub async fn list(&self) -> Vec<Ticket> {
+        self.store.read().await.list().cloned().collect()
+    }
+}
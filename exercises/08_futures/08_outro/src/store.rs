@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TicketId(pub u64);
+
+impl fmt::Display for TicketId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct TicketStore {
+    tickets: BTreeMap<TicketId, Ticket>,
+    counter: u64,
+}
+
+impl TicketStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_ticket(&mut self, draft: TicketDraft) -> &Ticket {
+        let id = TicketId(self.counter);
+        self.counter += 1;
+        let ticket = Ticket {
+            id,
+            title: draft.title,
+            description: draft.description,
+            status: Status::ToDo,
+        };
+        self.tickets.entry(id).or_insert(ticket)
+    }
+
+    pub fn get(&self, id: TicketId) -> Option<&Ticket> {
+        self.tickets.get(&id)
+    }
+
+    // Returns the updated ticket, or `None` if there's no ticket with that id.
+    pub fn update(&mut self, id: TicketId, patch: TicketPatch) -> Option<&Ticket> {
+        let ticket = self.tickets.get_mut(&id)?;
+        if let Some(title) = patch.title {
+            ticket.title = title;
+        }
+        if let Some(description) = patch.description {
+            ticket.description = description;
+        }
+        if let Some(status) = patch.status {
+            ticket.status = status;
+        }
+        Some(ticket)
+    }
+
+    // Every ticket, ordered by id.
+    pub fn list(&self) -> impl Iterator<Item = &Ticket> {
+        self.tickets.values()
+    }
+}
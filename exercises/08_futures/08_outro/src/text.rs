@@ -0,0 +1,446 @@
+// A line-based text protocol, for shell scripts and `nc`.
+//
+// Each request is a line, and gets a response:
+//  - `CREATE <title>\t<description>` -> `OK <id>`
+//  - `GET <id>` -> `TICKET <id>\t<status>\t<title>\t<description>`
+//  - `SET <id> <field>=<value>[\t<field>=<value>...]` -> the updated `TICKET`.
+//    Fields are `title`, `description` and `status`.
+//  - `LIST [status=<status>]` -> `TICKETS <n>`, followed by `n` `TICKET` lines, ordered by id.
+// Statuses are `to_do`, `in_progress` and `done`. `todo` is accepted too, but `to_do` is sent.
+//
+// Failures are reported as `ERR <code> <message>`, where `code` is one of `syntax`, `invalid`
+// or `not_found`. After an error the connection stays usable, unless the line was too long.
+// Requests must be UTF-8: other lines get an `ERR syntax` response.
+//
+// Backslashes, tabs and newlines inside values are escaped as `\\`, `\t` and `\n`.
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+
+use ticket_fields::{TicketDescription, TicketTitle};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch, UnknownStatus};
+use crate::shared::SharedStore;
+use crate::store::TicketId;
+
+// Longer requests are rejected, and the connection is closed.
+pub const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+// How long to wait before accepting connections again after a failure,
+// e.g. because we ran out of file descriptors.
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+// Displayed the way it's sent over the wire, after `ERR `.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("syntax {0}")]
+    Syntax(String),
+    #[error("invalid {0}")]
+    Invalid(String),
+    #[error("not_found There is no ticket with id {0}")]
+    NotFound(TicketId),
+}
+
+#[derive(Debug)]
+enum Request {
+    Create(TicketDraft),
+    Get(TicketId),
+    Set(TicketId, TicketPatch),
+    List(Option<Status>),
+}
+
+fn syntax(message: &str) -> ProtocolError {
+    ProtocolError::Syntax(message.to_owned())
+}
+
+impl FromStr for Request {
+    type Err = ProtocolError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (command, arguments) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "CREATE" => {
+                let (title, description) = arguments
+                    .split_once('\t')
+                    .ok_or_else(|| syntax("Expected `CREATE <title>\\t<description>`"))?;
+                let mut errors = Vec::new();
+                let title = check(
+                    &mut errors,
+                    "title",
+                    TicketTitle::try_from(unescape(title)?),
+                );
+                let description = check(
+                    &mut errors,
+                    "description",
+                    TicketDescription::try_from(unescape(description)?),
+                );
+                match (title, description) {
+                    (Some(title), Some(description)) => {
+                        Ok(Request::Create(TicketDraft { title, description }))
+                    }
+                    _ => Err(ProtocolError::Invalid(errors.join("; "))),
+                }
+            }
+            "GET" => Ok(Request::Get(parse_id(arguments)?)),
+            "SET" => {
+                let (id, assignments) = arguments
+                    .split_once(' ')
+                    .ok_or_else(|| syntax("Expected `SET <id> <field>=<value>`"))?;
+                let id = parse_id(id)?;
+                let mut patch = TicketPatch::default();
+                let mut errors = Vec::new();
+                for assignment in assignments.split('\t') {
+                    let (field, value) = assignment
+                        .split_once('=')
+                        .ok_or_else(|| syntax("Expected `<field>=<value>`"))?;
+                    let value = unescape(value)?;
+                    match field {
+                        "title" => {
+                            patch.title = check(&mut errors, field, TicketTitle::try_from(value))
+                        }
+                        "description" => {
+                            patch.description =
+                                check(&mut errors, field, TicketDescription::try_from(value))
+                        }
+                        "status" => patch.status = check(&mut errors, field, parse_status(&value)),
+                        _ => return Err(syntax(&format!("Unknown field `{field}`"))),
+                    }
+                }
+                if errors.is_empty() {
+                    Ok(Request::Set(id, patch))
+                } else {
+                    Err(ProtocolError::Invalid(errors.join("; ")))
+                }
+            }
+            "LIST" if arguments.is_empty() => Ok(Request::List(None)),
+            "LIST" => {
+                let status = arguments
+                    .strip_prefix("status=")
+                    .ok_or_else(|| syntax("Expected `LIST [status=<status>]`"))?;
+                let status = parse_status(status)
+                    .map_err(|e| ProtocolError::Invalid(format!("status: {e}")))?;
+                Ok(Request::List(Some(status)))
+            }
+            _ => Err(syntax(&format!("Unknown command `{command}`"))),
+        }
+    }
+}
+
+fn check<T, E: std::fmt::Display>(
+    errors: &mut Vec<String>,
+    field: &str,
+    value: Result<T, E>,
+) -> Option<T> {
+    value.map_err(|e| errors.push(format!("{field}: {e}"))).ok()
+}
+
+// Easier to type than `to_do`.
+fn parse_status(status: &str) -> Result<Status, UnknownStatus> {
+    match status {
+        "todo" => Ok(Status::ToDo),
+        _ => status.parse(),
+    }
+}
+
+fn parse_id(id: &str) -> Result<TicketId, ProtocolError> {
+    id.parse()
+        .map(TicketId)
+        .map_err(|_| syntax(&format!("`{id}` is not a ticket id")))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> Result<String, ProtocolError> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            _ => return Err(syntax("Invalid escape sequence")),
+        }
+    }
+    Ok(unescaped)
+}
+
+fn ticket_line(ticket: &Ticket) -> String {
+    format!(
+        "TICKET {}\t{}\t{}\t{}\n",
+        ticket.id.0,
+        ticket.status.as_str(),
+        escape(ticket.title.as_str()),
+        escape(ticket.description.as_str())
+    )
+}
+
+async fn respond(store: &SharedStore, line: &str) -> String {
+    let request = match line.parse() {
+        Ok(request) => request,
+        Err(e) => return format!("ERR {e}\n"),
+    };
+    let outcome = match request {
+        Request::Create(draft) => Ok(format!("OK {}\n", store.insert(draft).await.id)),
+        Request::Get(id) => store
+            .get(id)
+            .await
+            .map(|ticket| ticket_line(&ticket))
+            .ok_or(ProtocolError::NotFound(id)),
+        Request::Set(id, patch) => store
+            .patch(id, patch)
+            .await
+            .map(|ticket| ticket_line(&ticket))
+            .ok_or(ProtocolError::NotFound(id)),
+        Request::List(status) => {
+            let tickets: Vec<_> = store
+                .list()
+                .await
+                .into_iter()
+                .filter(|ticket| status.is_none_or(|status| ticket.status == status))
+                .collect();
+            let mut response = format!("TICKETS {}\n", tickets.len());
+            for ticket in &tickets {
+                response.push_str(&ticket_line(ticket));
+            }
+            Ok(response)
+        }
+    };
+    outcome.unwrap_or_else(|e| format!("ERR {e}\n"))
+}
+
+// Accepts connections until the task is cancelled, serving each of them on its own task.
+// Failing to accept a connection doesn't stop the server: it tries again.
+pub async fn serve(listener: TcpListener, store: SharedStore) -> io::Result<()> {
+    tracing::info!(address = %listener.local_addr()?, "Serving the text protocol");
+    let mut backoff = Duration::ZERO;
+    loop {
+        let connection = match listener.accept().await {
+            Ok((connection, _)) => connection,
+            // The peer gave up before we got to it: on to the next one.
+            Err(e) if is_transient(&e) => {
+                tracing::debug!(error = %e, "Failed to accept a connection");
+                continue;
+            }
+            // Retrying straight away would most likely fail the same way.
+            Err(e) => {
+                backoff = (backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+                tracing::warn!(error = %e, ?backoff, "Failed to accept a connection");
+                tokio::time::sleep(backoff).await;
+                continue;
+            }
+        };
+        backoff = Duration::ZERO;
+        let store = store.clone();
+        tokio::spawn(async move {
+            // The peer went away: nothing we can do about it.
+            let _ = handle_connection(connection, store).await;
+        });
+    }
+}
+
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+async fn handle_connection(connection: TcpStream, store: SharedStore) -> io::Result<()> {
+    let (reader, mut writer) = connection.into_split();
+    let mut reader = BufReader::new(reader);
+    while let Some(line) = read_line(&mut reader).await? {
+        let response = match line {
+            Line::Complete(line) => respond(&store, &line).await,
+            Line::NotUtf8 => format!("ERR {}\n", syntax("Requests must be UTF-8")),
+            Line::TooLong => {
+                let error = syntax(&format!("Lines are limited to {MAX_LINE_LENGTH} bytes"));
+                writer
+                    .write_all(format!("ERR {error}\n").as_bytes())
+                    .await?;
+                return Ok(());
+            }
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+enum Line {
+    // Without its line terminator.
+    Complete(String),
+    // We can't tell where the next line starts: there's no recovering from it.
+    TooLong,
+    NotUtf8,
+}
+
+// `None` at the end of the stream.
+async fn read_line<R>(reader: &mut BufReader<R>) -> io::Result<Option<Line>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut line = Vec::new();
+    let limit = MAX_LINE_LENGTH as u64 + 1;
+    if reader.take(limit).read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') && line.len() > MAX_LINE_LENGTH {
+        return Ok(Some(Line::TooLong));
+    }
+    let Ok(line) = String::from_utf8(line) else {
+        return Ok(Some(Line::NotUtf8));
+    };
+    Ok(Some(Line::Complete(
+        line.trim_end_matches(['\n', '\r']).to_owned(),
+    )))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TextClientError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Server(#[from] ProtocolError),
+    #[error("Unexpected response: {0:?}")]
+    Unexpected(String),
+}
+
+// Speaks the text protocol over a single connection.
+pub struct TextClient {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl TextClient {
+    pub async fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let (reader, writer) = TcpStream::connect(address).await?.into_split();
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer,
+        })
+    }
+
+    pub async fn create(&mut self, draft: &TicketDraft) -> Result<TicketId, TextClientError> {
+        let request = format!(
+            "CREATE {}\t{}\n",
+            escape(draft.title.as_str()),
+            escape(draft.description.as_str())
+        );
+        let line = self.request(&request).await?;
+        line.strip_prefix("OK ")
+            .and_then(|id| id.parse().ok())
+            .map(TicketId)
+            .ok_or(TextClientError::Unexpected(line))
+    }
+
+    pub async fn get(&mut self, id: TicketId) -> Result<Ticket, TextClientError> {
+        let line = self.request(&format!("GET {}\n", id.0)).await?;
+        parse_ticket(line)
+    }
+
+    pub async fn set(
+        &mut self,
+        id: TicketId,
+        patch: &TicketPatch,
+    ) -> Result<Ticket, TextClientError> {
+        let mut assignments = Vec::new();
+        if let Some(title) = &patch.title {
+            assignments.push(format!("title={}", escape(title.as_str())));
+        }
+        if let Some(description) = &patch.description {
+            assignments.push(format!("description={}", escape(description.as_str())));
+        }
+        if let Some(status) = patch.status {
+            assignments.push(format!("status={}", status.as_str()));
+        }
+        let request = format!("SET {} {}\n", id.0, assignments.join("\t"));
+        let line = self.request(&request).await?;
+        parse_ticket(line)
+    }
+
+    pub async fn list(&mut self, status: Option<Status>) -> Result<Vec<Ticket>, TextClientError> {
+        let request = match status {
+            Some(status) => format!("LIST status={}\n", status.as_str()),
+            None => "LIST\n".to_owned(),
+        };
+        let line = self.request(&request).await?;
+        let Some(count) = line.strip_prefix("TICKETS ").and_then(|n| n.parse().ok()) else {
+            return Err(TextClientError::Unexpected(line));
+        };
+        let mut tickets = Vec::with_capacity(count);
+        for _ in 0..count {
+            let line = self.read_response().await?;
+            tickets.push(parse_ticket(line)?);
+        }
+        Ok(tickets)
+    }
+
+    // Sends the request, then reads the first line of the response.
+    async fn request(&mut self, request: &str) -> Result<String, TextClientError> {
+        self.writer.write_all(request.as_bytes()).await?;
+        let line = self.read_response().await?;
+        match line.strip_prefix("ERR ") {
+            Some(error) => Err(parse_error(error).unwrap_or(TextClientError::Unexpected(line))),
+            None => Ok(line),
+        }
+    }
+
+    async fn read_response(&mut self) -> Result<String, TextClientError> {
+        match read_line(&mut self.reader).await? {
+            Some(Line::Complete(line)) => Ok(line),
+            Some(Line::TooLong) => Err(TextClientError::Unexpected("An overly long line".into())),
+            Some(Line::NotUtf8) => Err(TextClientError::Unexpected(
+                "A line that isn't UTF-8".into(),
+            )),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+}
+
+fn parse_error(error: &str) -> Option<TextClientError> {
+    let (code, message) = error.split_once(' ')?;
+    let error = match code {
+        "syntax" => ProtocolError::Syntax(message.to_owned()),
+        "invalid" => ProtocolError::Invalid(message.to_owned()),
+        "not_found" => {
+            let id = message.rsplit(' ').next()?.parse().ok()?;
+            ProtocolError::NotFound(TicketId(id))
+        }
+        _ => return None,
+    };
+    Some(error.into())
+}
+
+fn parse_ticket(line: String) -> Result<Ticket, TextClientError> {
+    let parse = |line: &str| {
+        let mut fields = line.strip_prefix("TICKET ")?.split('\t');
+        let id = TicketId(fields.next()?.parse().ok()?);
+        let status = fields.next()?.parse().ok()?;
+        let title = unescape(fields.next()?).ok()?.try_into().ok()?;
+        let description = unescape(fields.next()?).ok()?.try_into().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(Ticket {
+            id,
+            title,
+            description,
+            status,
+        })
+    };
+    parse(&line).ok_or(TextClientError::Unexpected(line))
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeSet;
+
+use outro_08::actor::{launch, ClientError};
+use outro_08::data::{Status, TicketDraft, TicketPatch};
+use outro_08::shared::SharedStore;
+use outro_08::store::TicketId;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+fn done() -> TicketPatch {
+    TicketPatch {
+        status: Some(Status::Done),
+        ..TicketPatch::default()
+    }
+}
+
+// A single worker thread: if anything blocked it, the test would hang.
+#[tokio::test(flavor = "current_thread")]
+async fn the_actor_serves_many_tasks_on_a_single_thread() {
+    let client = launch(4);
+    let tasks: Vec<_> = (0..50)
+        .map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move { client.insert(draft()).await.unwrap().id })
+        })
+        .collect();
+    let mut ids = BTreeSet::new();
+    for task in tasks {
+        ids.insert(task.await.unwrap());
+    }
+    assert_eq!(ids.len(), 50);
+
+    let id = *ids.first().unwrap();
+    let patched = client.patch(id, done()).await.unwrap().unwrap();
+    assert_eq!(patched.status, Status::Done);
+    assert_eq!(client.get(id).await.unwrap(), Some(patched));
+    assert_eq!(client.patch(TicketId(1_000), done()).await.unwrap(), None);
+    assert_eq!(client.list().await.unwrap().len(), 50);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn the_shared_store_serves_many_tasks_on_a_single_thread() {
+    let store = SharedStore::new();
+    let tasks: Vec<_> = (0..50)
+        .map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                let ticket = store.insert(draft()).await;
+                store.patch(ticket.id, done()).await.unwrap()
+            })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(task.await.unwrap().status, Status::Done);
+    }
+    let tickets = store.list().await;
+    assert_eq!(tickets.len(), 50);
+    assert!(tickets.windows(2).all(|pair| pair[0].id < pair[1].id));
+    assert_eq!(store.get(TicketId(1_000)).await, None);
+}
+
+#[test]
+fn clients_outliving_the_actor_are_disconnected() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = runtime.block_on(async { launch(4) });
+    // Shutting the runtime down drops the actor's task.
+    drop(runtime);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let outcome = runtime.block_on(client.get(TicketId(0)));
+    assert_eq!(outcome, Err(ClientError::Disconnected));
+}
+
+// Room for a single command: most tasks wait for room in the queue, without blocking the thread.
+#[tokio::test(flavor = "current_thread")]
+async fn tasks_wait_for_room_in_a_full_queue() {
+    let client = launch(1);
+    let id = client.insert(draft()).await.unwrap().id;
+    let tasks: Vec<_> = (0..20)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                match i % 2 {
+                    0 => client.patch(id, done()).await.unwrap().unwrap(),
+                    _ => client.insert(draft()).await.unwrap(),
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(client.list().await.unwrap().len(), 11);
+    assert_eq!(client.get(id).await.unwrap().unwrap().status, Status::Done);
+}
+
+#[test]
+fn queued_requests_are_dropped_with_the_actor() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let client = runtime.block_on(async { launch(4) });
+    // Queue the request without giving the actor a chance to run.
+    let mut request = Box::pin(async move { client.get(TicketId(0)).await });
+    runtime.block_on(async {
+        tokio::select! {
+            biased;
+            _ = &mut request => unreachable!("The actor never ran"),
+            _ = std::future::ready(()) => {}
+        }
+    });
+    drop(runtime);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let outcome = runtime.block_on(request);
+    assert_eq!(outcome, Err(ClientError::RequestDropped));
+}
@@ -0,0 +1,222 @@
+use outro_08::api::serve;
+use outro_08::shared::SharedStore;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use tokio::net::TcpListener;
+
+// Starts a server on a random port, returning its base URL.
+async fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, SharedStore::default()));
+    format!("http://{address}")
+}
+
+fn draft() -> Value {
+    json!({
+        "title": ticket_title().as_str(),
+        "description": ticket_description().as_str(),
+    })
+}
+
+#[tokio::test]
+async fn create_retrieve_patch_and_list() {
+    let base = start().await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{base}/tickets"))
+        .json(&draft())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["title"], ticket_title().as_str());
+    assert_eq!(created["status"], "to_do");
+    let id = created["id"].as_u64().unwrap();
+
+    let retrieved: Value = client
+        .get(format!("{base}/tickets/{id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(retrieved, created);
+
+    let response = client
+        .patch(format!("{base}/tickets/{id}"))
+        .json(&json!({ "title": "Renamed", "status": "in_progress" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let patched: Value = response.json().await.unwrap();
+    assert_eq!(patched["title"], "Renamed");
+    assert_eq!(patched["description"], created["description"]);
+    assert_eq!(patched["status"], "in_progress");
+
+    client
+        .post(format!("{base}/tickets"))
+        .json(&draft())
+        .send()
+        .await
+        .unwrap();
+    let listed: Vec<Value> = client
+        .get(format!("{base}/tickets"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0], patched);
+}
+
+#[tokio::test]
+async fn invalid_fields_and_unknown_ids() {
+    let base = start().await;
+    let client = Client::new();
+
+    let response = client
+        .post(format!("{base}/tickets"))
+        .json(&json!({ "title": "", "description": "x".repeat(501) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body,
+        json!({ "errors": {
+            "title": "The title cannot be empty",
+            "description": "The description cannot be longer than 500 bytes",
+        }})
+    );
+
+    let response = client
+        .post(format!("{base}/tickets"))
+        .json(&json!({ "description": ticket_description().as_str() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body,
+        json!({ "errors": { "title": "The title is required" } })
+    );
+
+    let response = client
+        .get(format!("{base}/tickets/42"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Invalid patches are rejected as a whole.
+    let created: Value = client
+        .post(format!("{base}/tickets"))
+        .json(&draft())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_u64().unwrap();
+    let response = client
+        .patch(format!("{base}/tickets/{id}"))
+        .json(&json!({ "title": "Renamed", "status": "stalled" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["errors"]["status"]
+        .as_str()
+        .unwrap()
+        .contains("stalled"));
+    let retrieved: Value = client
+        .get(format!("{base}/tickets/{id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(retrieved, created);
+
+    let response = client
+        .patch(format!("{base}/tickets/42"))
+        .json(&json!({ "status": "done" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unreadable_bodies_are_reported_like_invalid_fields() {
+    let base = start().await;
+    let client = Client::new();
+
+    let malformed = client
+        .post(format!("{base}/tickets"))
+        .header("content-type", "application/json")
+        .body("{\"title\": ")
+        .send()
+        .await
+        .unwrap();
+    let mistyped = client
+        .post(format!("{base}/tickets"))
+        .json(&json!({ "title": 42, "description": "Typed" }))
+        .send()
+        .await
+        .unwrap();
+    let untyped = client
+        .post(format!("{base}/tickets"))
+        .body(draft().to_string())
+        .send()
+        .await
+        .unwrap();
+    for response in [malformed, mistyped, untyped] {
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = response.json().await.unwrap();
+        assert!(body["errors"]["body"].is_string(), "{body}");
+    }
+
+    // Patches too, and nothing gets created or changed along the way.
+    let created: Value = client
+        .post(format!("{base}/tickets"))
+        .json(&draft())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_u64().unwrap();
+    let response = client
+        .patch(format!("{base}/tickets/{id}"))
+        .json(&json!({ "status": ["done"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["errors"]["body"].as_str().unwrap().contains("status"));
+    let listed: Vec<Value> = client
+        .get(format!("{base}/tickets"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed, vec![created]);
+}
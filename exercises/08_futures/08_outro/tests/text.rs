@@ -0,0 +1,145 @@
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+
+use outro_08::data::{Status, TicketDraft, TicketPatch};
+use outro_08::shared::SharedStore;
+use outro_08::text::{serve, ProtocolError, TextClient, TextClientError, MAX_LINE_LENGTH};
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, SharedStore::default()));
+    address
+}
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+#[tokio::test]
+async fn raw_lines_get_well_defined_responses() {
+    let address = start().await;
+    let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut exchange = async |request: &str| {
+        writer.write_all(request.as_bytes()).await.unwrap();
+        lines.next_line().await.unwrap().unwrap()
+    };
+
+    assert_eq!(exchange("CREATE Fix the build\tIt's red\n").await, "OK 0");
+    assert_eq!(
+        exchange("SET 0 status=done\ttitle=Fixed\\tthe build\r\n").await,
+        "TICKET 0\tdone\tFixed\\tthe build\tIt's red"
+    );
+    assert_eq!(exchange("LIST status=to_do\n").await, "TICKETS 0");
+    assert_eq!(
+        exchange("GET 7\n").await,
+        "ERR not_found There is no ticket with id 7"
+    );
+    assert_eq!(
+        exchange("CREATE \t\n").await,
+        "ERR invalid title: The title cannot be empty; description: The description cannot be empty"
+    );
+    assert_eq!(
+        exchange("DELETE 0\n").await,
+        "ERR syntax Unknown command `DELETE`"
+    );
+    assert_eq!(
+        exchange("GET zero\n").await,
+        "ERR syntax `zero` is not a ticket id"
+    );
+    // The connection survives errors.
+    assert_eq!(
+        exchange("GET 0\n").await,
+        "TICKET 0\tdone\tFixed\\tthe build\tIt's red"
+    );
+}
+
+#[tokio::test]
+async fn many_concurrent_clients() {
+    let address = start().await;
+    let clients: Vec<_> = (0..20)
+        .map(|_| {
+            tokio::spawn(async move {
+                let mut client = TextClient::connect(address).await.unwrap();
+                let mut ids = Vec::new();
+                for _ in 0..5 {
+                    ids.push(client.create(&draft()).await.unwrap());
+                }
+                ids
+            })
+        })
+        .collect();
+    let mut ids = BTreeSet::new();
+    for client in clients {
+        ids.extend(client.await.unwrap());
+    }
+    assert_eq!(ids.len(), 100);
+
+    let mut client = TextClient::connect(address).await.unwrap();
+    let id = *ids.first().unwrap();
+    let patch = TicketPatch {
+        status: Some(Status::InProgress),
+        description: Some("Tabs\tand\nnewlines".try_into().unwrap()),
+        ..TicketPatch::default()
+    };
+    let updated = client.set(id, &patch).await.unwrap();
+    assert_eq!(updated.status, Status::InProgress);
+    assert_eq!(client.get(id).await.unwrap(), updated);
+    assert_eq!(client.list(None).await.unwrap().len(), 100);
+    assert_eq!(
+        client.list(Some(Status::InProgress)).await.unwrap(),
+        vec![updated]
+    );
+
+    let missing = outro_08::store::TicketId(1_000);
+    assert!(matches!(
+        client.get(missing).await,
+        Err(TextClientError::Server(ProtocolError::NotFound(id))) if id == missing
+    ));
+}
+
+#[tokio::test]
+async fn malformed_lines_are_answered_too() {
+    let address = start().await;
+    let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    writer
+        .write_all(b"CREATE Fix the build\tIt's red\n")
+        .await
+        .unwrap();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "OK 0");
+    writer.write_all(b"GET \xff\xfe\n").await.unwrap();
+    assert_eq!(
+        lines.next_line().await.unwrap().unwrap(),
+        "ERR syntax Requests must be UTF-8"
+    );
+    // The connection survives it, and `todo` is understood.
+    writer.write_all(b"LIST status=todo\n").await.unwrap();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "TICKETS 1");
+    lines.next_line().await.unwrap().unwrap();
+    writer.write_all(b"LIST status=stalled\n").await.unwrap();
+    assert!(lines
+        .next_line()
+        .await
+        .unwrap()
+        .unwrap()
+        .starts_with("ERR invalid status: `stalled` is not a status"));
+
+    // An overly long line is answered, then the connection is closed.
+    let line = format!("GET {}\n", "0".repeat(MAX_LINE_LENGTH));
+    writer.write_all(line.as_bytes()).await.unwrap();
+    assert_eq!(
+        lines.next_line().await.unwrap().unwrap(),
+        format!("ERR syntax Lines are limited to {MAX_LINE_LENGTH} bytes")
+    );
+    // What's left of the line was never read: the peer may reset the connection.
+    assert!(!matches!(lines.next_line().await, Ok(Some(_))));
+}
@@ -0,0 +1,668 @@
+// Request/response actors over bounded mailboxes.
+//
+// An actor owns its state and processes its messages one at a time, on its own thread.
+// Messages that expect a response carry a `Reply`: the requester keeps the matching
+// `Pending` and waits on it, either blocking or with `.await`.
+// Sending can be done either way too: async senders wait for room in the mailbox
+// without blocking their thread.
+use std::cell::Cell;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+mod reply;
+
+pub use reply::{oneshot, Pending, Reply, WaitError};
+
+pub trait Actor: Send + 'static {
+    type Message: Send + 'static;
+
+    // Return `ControlFlow::Break` to stop the actor: messages that are already
+    // in the mailbox are still handled, then `Mailbox::run` returns.
+    fn handle(&mut self, message: Self::Message) -> ControlFlow<()>;
+}
+
+// Decides the order in which queued messages are handled: see `Mailbox::run_scheduled`.
+pub trait Scheduler<M> {
+    fn push(&mut self, message: M);
+    // The next message to handle, if there's any.
+    fn pop(&mut self) -> Option<M>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum RequestError {
+    #[error("The actor's mailbox is full")]
+    Full,
+    #[error("The actor is not running")]
+    Disconnected,
+    #[error(transparent)]
+    Wait(#[from] WaitError),
+}
+
+// Creates a mailbox with room for `capacity` messages, and a handle to send messages to it.
+pub fn mailbox<M>(capacity: usize) -> (ActorHandle<M>, Mailbox<M>) {
+    let (sender, lane) = lane(capacity);
+    let handle = ActorHandle {
+        sender,
+        room: lane.room.clone(),
+        arrivals: None,
+    };
+    (handle, Mailbox::new(vec![lane], None))
+}
+
+// Like `mailbox`, but messages are sorted into lanes as they're sent: there's one handle per lane.
+// Each lane has room for `capacity` messages of its own, so a full lane doesn't hold up the others.
+// The mailbox takes messages from each lane in turn.
+pub fn mailbox_with_lanes<M>(capacity: usize, lanes: usize) -> (Vec<ActorHandle<M>>, Mailbox<M>) {
+    assert!(lanes > 0, "A mailbox needs at least one lane");
+    if lanes == 1 {
+        let (handle, mailbox) = mailbox(capacity);
+        return (vec![handle], mailbox);
+    }
+    let arrivals = Arc::new(Arrivals {
+        senders: Mutex::new(lanes),
+        arrived: Condvar::new(),
+    });
+    let (handles, lanes) = (0..lanes)
+        .map(|_| {
+            let (sender, lane) = lane(capacity);
+            let handle = ActorHandle {
+                sender,
+                room: lane.room.clone(),
+                arrivals: Some(arrivals.clone()),
+            };
+            (handle, lane)
+        })
+        .unzip();
+    (handles, Mailbox::new(lanes, Some(arrivals)))
+}
+
+fn lane<M>(capacity: usize) -> (SyncSender<M>, Lane<M>) {
+    let (sender, receiver) = sync_channel(capacity);
+    let lane = Lane {
+        receiver,
+        room: Arc::default(),
+    };
+    (sender, lane)
+}
+
+// Senders that found the mailbox full, and are waiting for it to have room:
+// async ones leave a waker, blocking ones wait on `freed`.
+#[derive(Default)]
+struct Room {
+    waiting: Mutex<Waiting>,
+    freed: Condvar,
+}
+
+#[derive(Default)]
+struct Waiting {
+    wakers: Vec<Waker>,
+    // The mailbox is gone: there will never be room again.
+    closed: bool,
+}
+
+// How a mailbox with several lanes waits for a message on any of them:
+// handles tell it whenever they send one.
+struct Arrivals {
+    // Handles still around, across lanes. Once they're all gone, nothing can arrive anymore.
+    senders: Mutex<usize>,
+    arrived: Condvar,
+}
+
+// Runs the actor on a new thread. The thread returns the actor once it stops.
+pub fn spawn<A: Actor>(mut actor: A, capacity: usize) -> (ActorHandle<A::Message>, JoinHandle<A>) {
+    let (handle, mailbox) = mailbox(capacity);
+    let thread = std::thread::spawn(move || {
+        mailbox.run(&mut actor);
+        actor
+    });
+    (handle, thread)
+}
+
+pub struct Mailbox<M> {
+    lanes: Vec<Lane<M>>,
+    // Only for mailboxes with several lanes: there's no single channel to block on.
+    arrivals: Option<Arc<Arrivals>>,
+    // The lane to look at first: lanes take turns.
+    next: Cell<usize>,
+}
+
+struct Lane<M> {
+    receiver: Receiver<M>,
+    room: Arc<Room>,
+}
+
+impl<M> Mailbox<M> {
+    fn new(lanes: Vec<Lane<M>>, arrivals: Option<Arc<Arrivals>>) -> Self {
+        Self {
+            lanes,
+            arrivals,
+            next: Cell::new(0),
+        }
+    }
+
+    // Feeds messages to the actor until it asks to stop or every handle has been dropped.
+    // If the actor panics, the mailbox (and the messages in it) survive: `run` can be called again.
+    pub fn run<A>(&self, actor: &mut A)
+    where
+        A: Actor<Message = M>,
+    {
+        while let Some(message) = self.recv() {
+            if actor.handle(message).is_break() {
+                // Messages that were queued before we were asked to stop are handled too.
+                while let Some(message) = self.try_recv() {
+                    let _ = actor.handle(message);
+                }
+                return;
+            }
+        }
+    }
+
+    // Like `run`, but the scheduler picks which message to handle next.
+    // Up to `lookahead` messages are moved out of the mailbox for it to choose from.
+    // They belong to the scheduler from then on: if the actor panics, they're still there
+    // when `run_scheduled` is called again with the same scheduler.
+    pub fn run_scheduled<A, S>(&self, actor: &mut A, scheduler: &mut S, lookahead: usize)
+    where
+        A: Actor<Message = M>,
+        S: Scheduler<M>,
+    {
+        loop {
+            if scheduler.is_empty() {
+                let Some(message) = self.recv() else {
+                    return;
+                };
+                scheduler.push(message);
+            }
+            while scheduler.len() < lookahead {
+                let Some(message) = self.try_recv() else {
+                    break;
+                };
+                scheduler.push(message);
+            }
+            let Some(message) = scheduler.pop() else {
+                continue;
+            };
+            if actor.handle(message).is_break() {
+                while let Some(message) = self.try_recv() {
+                    scheduler.push(message);
+                }
+                while let Some(message) = scheduler.pop() {
+                    let _ = actor.handle(message);
+                }
+                return;
+            }
+        }
+    }
+
+    fn recv(&self) -> Option<M> {
+        let Some(arrivals) = &self.arrivals else {
+            let lane = &self.lanes[0];
+            let message = lane.receiver.recv().ok()?;
+            lane.made_room();
+            return Some(message);
+        };
+        let mut senders = arrivals.senders.lock().unwrap();
+        loop {
+            // Tried while holding the lock: a message sent right after we find every lane empty
+            // is only announced once we're waiting.
+            if let Some(message) = self.try_recv() {
+                return Some(message);
+            }
+            if *senders == 0 {
+                return None;
+            }
+            senders = arrivals.arrived.wait(senders).unwrap();
+        }
+    }
+
+    fn try_recv(&self) -> Option<M> {
+        let lanes = self.lanes.len();
+        let first = self.next.get();
+        (first..first + lanes).find_map(|lane| {
+            let lane = lane % lanes;
+            let message = self.lanes[lane].receiver.try_recv().ok()?;
+            self.lanes[lane].made_room();
+            self.next.set((lane + 1) % lanes);
+            Some(message)
+        })
+    }
+}
+
+impl<M> Lane<M> {
+    fn made_room(&self) {
+        let wakers = std::mem::take(&mut self.room.waiting.lock().unwrap().wakers);
+        self.room.freed.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl<M> Drop for Mailbox<M> {
+    fn drop(&mut self) {
+        // Waiting senders find out that the actor is gone.
+        for lane in &self.lanes {
+            let mut waiting = lane.room.waiting.lock().unwrap();
+            waiting.closed = true;
+            lane.room.freed.notify_all();
+            waiting.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+pub struct ActorHandle<M> {
+    sender: SyncSender<M>,
+    room: Arc<Room>,
+    arrivals: Option<Arc<Arrivals>>,
+}
+
+// Not derived: `M` doesn't need to be `Clone`.
+impl<M> Clone for ActorHandle<M> {
+    fn clone(&self) -> Self {
+        if let Some(arrivals) = &self.arrivals {
+            *arrivals.senders.lock().unwrap() += 1;
+        }
+        Self {
+            sender: self.sender.clone(),
+            room: self.room.clone(),
+            arrivals: self.arrivals.clone(),
+        }
+    }
+}
+
+impl<M> Drop for ActorHandle<M> {
+    fn drop(&mut self) {
+        if let Some(arrivals) = &self.arrivals {
+            let mut senders = arrivals.senders.lock().unwrap();
+            *senders -= 1;
+            if *senders == 0 {
+                arrivals.arrived.notify_all();
+            }
+        }
+    }
+}
+
+impl<M> ActorHandle<M> {
+    // Fails straight away if the mailbox is full.
+    pub fn try_send(&self, message: M) -> Result<(), TrySendError<M>> {
+        self.sender.try_send(message)?;
+        self.arrived();
+        Ok(())
+    }
+
+    // Blocks until the mailbox has room. Gives the message back if the actor is gone.
+    pub fn send(&self, message: M) -> Result<(), M> {
+        self.sender.send(message).map_err(|e| e.0)?;
+        self.arrived();
+        Ok(())
+    }
+
+    // Like `send`, but gives up once `timeout` has elapsed: the message then comes back as `Full`.
+    pub fn send_timeout(&self, message: M, timeout: Duration) -> Result<(), TrySendError<M>> {
+        let deadline = Instant::now() + timeout;
+        let mut waiting = self.room.waiting.lock().unwrap();
+        let mut message = message;
+        loop {
+            // Tried while holding the lock: if room is made right after we find the mailbox full,
+            // we're already waiting to be told.
+            message = match self.sender.try_send(message) {
+                Ok(()) => {
+                    drop(waiting);
+                    self.arrived();
+                    return Ok(());
+                }
+                Err(TrySendError::Full(message)) => message,
+                Err(disconnected) => return Err(disconnected),
+            };
+            if waiting.closed {
+                return Err(TrySendError::Disconnected(message));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(TrySendError::Full(message));
+            }
+            waiting = self
+                .room
+                .freed
+                .wait_timeout(waiting, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    // Wakes up a mailbox with several lanes, in case it's waiting for a message.
+    fn arrived(&self) {
+        if let Some(arrivals) = &self.arrivals {
+            let _senders = arrivals.senders.lock().unwrap();
+            arrivals.arrived.notify_all();
+        }
+    }
+
+    // Like `send`, but waits for the mailbox to have room without blocking the thread.
+    pub fn send_async(&self, message: M) -> Sending<'_, M> {
+        Sending {
+            handle: self,
+            message: Some(message),
+        }
+    }
+
+    // Sends a request, blocking until the mailbox has room if necessary,
+    // and returns without waiting for the response: the `Pending` response can be awaited.
+    pub fn send_request<T, F>(&self, message: F) -> Result<Pending<T>, RequestError>
+    where
+        F: FnOnce(Reply<T>) -> M,
+    {
+        let (reply, pending) = oneshot();
+        self.send(message(reply))
+            .map_err(|_| RequestError::Disconnected)?;
+        Ok(pending)
+    }
+
+    // Sends a request and awaits the response. Never blocks the thread,
+    // not even to wait for the mailbox to have room.
+    pub async fn request_async<T, F>(&self, message: F) -> Result<T, RequestError>
+    where
+        F: FnOnce(Reply<T>) -> M,
+    {
+        let (reply, pending) = oneshot();
+        self.send_async(message(reply))
+            .await
+            .map_err(|_| RequestError::Disconnected)?;
+        Ok(pending.await?)
+    }
+
+    // Sends a request and blocks until the actor responds.
+    pub fn request<T, F>(&self, message: F) -> Result<T, RequestError>
+    where
+        F: FnOnce(Reply<T>) -> M,
+    {
+        Ok(self.send_request(message)?.wait()?)
+    }
+
+    // Sends a request only if the mailbox has room, then waits for up to `timeout` for the response.
+    pub fn try_request<T, F>(&self, message: F, timeout: Duration) -> Result<T, RequestError>
+    where
+        F: FnOnce(Reply<T>) -> M,
+    {
+        let (reply, pending) = oneshot();
+        self.try_send(message(reply)).map_err(|e| match e {
+            TrySendError::Full(_) => RequestError::Full,
+            TrySendError::Disconnected(_) => RequestError::Disconnected,
+        })?;
+        Ok(pending.wait_timeout(timeout)?)
+    }
+}
+
+// Returned by `ActorHandle::send_async`.
+// Resolves once the message is in the mailbox, or to the message itself if the actor is gone.
+pub struct Sending<'a, M> {
+    handle: &'a ActorHandle<M>,
+    message: Option<M>,
+}
+
+// The message is never pinned: we only ever move it into the mailbox.
+impl<M> Unpin for Sending<'_, M> {}
+
+impl<M> Future for Sending<'_, M> {
+    type Output = Result<(), M>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let message = self.message.take().expect("polled after completion");
+        {
+            let mut waiting = self.handle.room.waiting.lock().unwrap();
+            if waiting.closed {
+                return Poll::Ready(Err(message));
+            }
+            // Registered before trying: if room is made right after we find the mailbox full,
+            // we still get woken up.
+            if !waiting.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                waiting.wakers.push(cx.waker().clone());
+            }
+        }
+        match self.handle.try_send(message) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(TrySendError::Disconnected(message)) => Poll::Ready(Err(message)),
+            Err(TrySendError::Full(message)) => {
+                self.message = Some(message);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::Wake;
+
+    // Raised when a future asks to be polled again.
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    struct Counter {
+        total: u64,
+    }
+
+    enum Message {
+        Add(u64, Reply<u64>),
+        Sleep(Duration),
+        Stop,
+        Panic,
+    }
+
+    impl Actor for Counter {
+        type Message = Message;
+
+        fn handle(&mut self, message: Message) -> ControlFlow<()> {
+            match message {
+                Message::Add(n, reply) => {
+                    self.total += n;
+                    reply.send(self.total);
+                }
+                Message::Sleep(duration) => std::thread::sleep(duration),
+                Message::Stop => return ControlFlow::Break(()),
+                Message::Panic => panic!("Boom"),
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn requests_and_graceful_stop() {
+        let (handle, thread) = spawn(Counter { total: 0 }, 4);
+        assert_eq!(handle.request(|reply| Message::Add(2, reply)), Ok(2));
+        let pending = handle.send_request(|reply| Message::Add(3, reply)).unwrap();
+        handle.send(Message::Stop).ok().unwrap();
+        assert_eq!(pending.wait(), Ok(5));
+        assert_eq!(thread.join().unwrap().total, 5);
+        assert_eq!(
+            handle.request(|reply| Message::Add(1, reply)),
+            Err(RequestError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn full_mailboxes_and_timeouts() {
+        let (handle, _thread) = spawn(Counter { total: 0 }, 1);
+        handle
+            .send(Message::Sleep(Duration::from_millis(100)))
+            .ok()
+            .unwrap();
+        // Give the actor time to pick up the message.
+        std::thread::sleep(Duration::from_millis(10));
+        let timeout = Duration::from_millis(10);
+        // The actor is busy: this one sits in the mailbox...
+        assert_eq!(
+            handle.try_request(|reply| Message::Add(1, reply), timeout),
+            Err(RequestError::Wait(WaitError::TimedOut(timeout)))
+        );
+        // ...so there's no room for this one.
+        assert_eq!(
+            handle.try_request(|reply| Message::Add(1, reply), timeout),
+            Err(RequestError::Full)
+        );
+    }
+
+    #[test]
+    fn timed_sends_wait_for_room() {
+        let (handle, mailbox) = mailbox(1);
+        handle.send(1).unwrap();
+        let timeout = Duration::from_millis(10);
+        assert_eq!(handle.send_timeout(2, timeout), Err(TrySendError::Full(2)));
+
+        std::thread::scope(|scope| {
+            let sending = scope.spawn(|| handle.send_timeout(3, Duration::from_secs(5)));
+            assert_eq!(mailbox.recv(), Some(1));
+            assert_eq!(sending.join().unwrap(), Ok(()));
+        });
+        assert_eq!(mailbox.try_recv(), Some(3));
+
+        drop(mailbox);
+        let disconnected = handle.send_timeout(4, Duration::from_secs(5));
+        assert_eq!(disconnected, Err(TrySendError::Disconnected(4)));
+    }
+
+    #[test]
+    fn lanes_take_turns_and_fill_up_on_their_own() {
+        let (handles, mailbox) = mailbox_with_lanes(2, 2);
+        let [first, second] = &handles[..] else {
+            unreachable!()
+        };
+        first.send(1).unwrap();
+        first.send(2).unwrap();
+        assert_eq!(first.try_send(3), Err(TrySendError::Full(3)));
+        second.send(10).unwrap();
+        assert_eq!(mailbox.try_recv(), Some(1));
+        assert_eq!(mailbox.try_recv(), Some(10));
+        assert_eq!(mailbox.try_recv(), Some(2));
+        assert_eq!(mailbox.try_recv(), None);
+
+        let receiving = std::thread::spawn(move || (mailbox.recv(), mailbox));
+        second.send(20).unwrap();
+        let (received, mailbox) = receiving.join().unwrap();
+        assert_eq!(received, Some(20));
+        // Once every handle is gone, so are the messages.
+        first.send(4).unwrap();
+        drop(handles);
+        assert_eq!(mailbox.recv(), Some(4));
+        assert_eq!(mailbox.recv(), None);
+    }
+
+    #[test]
+    fn mailboxes_survive_panics() {
+        let (handle, mailbox) = mailbox(4);
+        let mut counter = Counter { total: 0 };
+        let pending = handle.send_request(|reply| Message::Add(1, reply)).unwrap();
+        handle.send(Message::Panic).ok().unwrap();
+        let doomed = handle.send_request(|reply| Message::Add(1, reply)).unwrap();
+        handle.send(Message::Stop).ok().unwrap();
+        let crashed =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| mailbox.run(&mut counter)));
+        assert!(crashed.is_err());
+        assert_eq!(pending.wait(), Ok(1));
+        // The mailbox survived the panic: we can pick up where we left off.
+        mailbox.run(&mut counter);
+        assert_eq!(doomed.wait(), Ok(2));
+    }
+
+    #[test]
+    fn schedulers_pick_the_next_message() {
+        struct Lifo(Vec<Message>);
+        impl Scheduler<Message> for Lifo {
+            fn push(&mut self, message: Message) {
+                self.0.push(message);
+            }
+            fn pop(&mut self) -> Option<Message> {
+                self.0.pop()
+            }
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+        }
+
+        let (handle, mailbox) = mailbox(4);
+        let first = handle.send_request(|reply| Message::Add(1, reply)).unwrap();
+        let second = handle
+            .send_request(|reply| Message::Add(10, reply))
+            .unwrap();
+        handle.send(Message::Stop).ok().unwrap();
+        let mut counter = Counter { total: 0 };
+        mailbox.run_scheduled(&mut counter, &mut Lifo(Vec::new()), 4);
+        // Newest first: `Stop` is handled straight away, then the queued messages are drained.
+        assert_eq!(second.wait(), Ok(10));
+        assert_eq!(first.wait(), Ok(11));
+    }
+
+    #[test]
+    fn pending_responses_can_be_awaited() {
+        let (reply, pending) = oneshot();
+        let flag = Arc::new(Flag(Default::default()));
+        let waker = flag.clone().into();
+        let mut cx = Context::from_waker(&waker);
+        let mut pending = pin!(pending);
+        assert_eq!(pending.as_mut().poll(&mut cx), Poll::Pending);
+        reply.send(42);
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert_eq!(pending.poll(&mut cx), Poll::Ready(Ok(42)));
+
+        let (reply, pending) = oneshot::<()>();
+        drop(reply);
+        assert_eq!(
+            pin!(pending).poll(&mut cx),
+            Poll::Ready(Err(WaitError::Dropped))
+        );
+    }
+
+    #[test]
+    fn async_sends_wait_for_room_without_blocking() {
+        let (handle, mailbox) = mailbox(2);
+        let flag = Arc::new(Flag(Default::default()));
+        let waker = flag.clone().into();
+        let mut cx = Context::from_waker(&waker);
+
+        handle.send(Message::Stop).ok().unwrap();
+        handle.send(Message::Stop).ok().unwrap();
+        let mut response = pin!(handle.request_async(|reply| Message::Add(1, reply)));
+        // The mailbox is full: the request waits instead of blocking the thread.
+        assert_eq!(response.as_mut().poll(&mut cx), Poll::Pending);
+        assert!(!flag.0.load(Ordering::SeqCst));
+
+        // Making room wakes it up.
+        let mut counter = Counter { total: 0 };
+        mailbox.run(&mut counter);
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert_eq!(response.as_mut().poll(&mut cx), Poll::Pending);
+        handle.send(Message::Stop).ok().unwrap();
+        mailbox.run(&mut counter);
+        assert_eq!(response.poll(&mut cx), Poll::Ready(Ok(1)));
+
+        // So does the actor going away.
+        handle.send(Message::Stop).ok().unwrap();
+        handle.send(Message::Stop).ok().unwrap();
+        flag.0.store(false, Ordering::SeqCst);
+        let mut stopped = pin!(handle.send_async(Message::Stop));
+        assert!(stopped.as_mut().poll(&mut cx).is_pending());
+        drop(mailbox);
+        assert!(flag.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            stopped.poll(&mut cx),
+            Poll::Ready(Err(Message::Stop))
+        ));
+    }
+}
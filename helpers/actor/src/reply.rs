@@ -0,0 +1,125 @@
+// A single-use channel, carrying the response to a request back to whoever sent it.
+//
+// The receiving end can be waited on from a thread (`wait`, `wait_timeout`)
+// or awaited from async code: `Pending` is a `Future`.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum WaitError {
+    #[error("The actor dropped the request without responding")]
+    Dropped,
+    #[error("The actor didn't respond within {0:?}")]
+    TimedOut(Duration),
+}
+
+// Creates a connected `Reply`/`Pending` pair.
+pub fn oneshot<T>() -> (Reply<T>, Pending<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            value: None,
+            closed: false,
+            waker: None,
+        }),
+        ready: Condvar::new(),
+    });
+    (
+        Reply {
+            shared: shared.clone(),
+        },
+        Pending { shared },
+    )
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+}
+
+struct State<T> {
+    value: Option<T>,
+    // The `Reply` is gone: no value is coming, if there isn't one already.
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+// The sending end: the actor uses it to respond.
+pub struct Reply<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Reply<T> {
+    // If the requester has stopped waiting, the value is just dropped.
+    pub fn send(self, value: T) {
+        self.shared.state.lock().unwrap().value = Some(value);
+        // Dropping `self` wakes up the receiving end.
+    }
+}
+
+impl<T> Drop for Reply<T> {
+    fn drop(&mut self) {
+        // This runs while unwinding too: the lock may be poisoned,
+        // but the state it guards is always consistent.
+        let mut state = self
+            .shared
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.shared.ready.notify_all();
+    }
+}
+
+// The receiving end: the requester uses it to get the response.
+pub struct Pending<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Pending<T> {
+    // Blocks until the actor responds.
+    pub fn wait(self) -> Result<T, WaitError> {
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.closed {
+            state = self.shared.ready.wait(state).unwrap();
+        }
+        state.value.take().ok_or(WaitError::Dropped)
+    }
+
+    // Blocks until the actor responds, for up to `timeout`.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<T, WaitError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(WaitError::TimedOut(timeout));
+            }
+            state = self
+                .shared
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+        state.value.take().ok_or(WaitError::Dropped)
+    }
+}
+
+impl<T> Future for Pending<T> {
+    type Output = Result<T, WaitError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed {
+            return Poll::Ready(state.value.take().ok_or(WaitError::Dropped));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
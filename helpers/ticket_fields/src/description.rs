@@ -27,6 +27,12 @@ impl TryFrom<&str> for TicketDescription {
     }
 }
 
+impl TicketDescription {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 fn validate(description: &str) -> Result<(), TicketDescriptionError> {
     if description.is_empty() {
         Err(TicketDescriptionError::Empty)
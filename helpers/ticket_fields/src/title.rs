@@ -29,6 +29,12 @@ impl TryFrom<&str> for TicketTitle {
     }
 }
 
+impl TicketTitle {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 fn validate(title: &str) -> Result<(), TicketTitleError> {
     if title.is_empty() {
         Err(TicketTitleError::Empty)
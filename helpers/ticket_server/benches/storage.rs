@@ -0,0 +1,132 @@
+// Compares the two storage layouts `TicketStore` can run on:
+// `MapStorage` (a `BTreeMap` of boxed tickets) and `ArenaStorage` (tickets inline in chunks).
+// Run with `cargo bench -p ticket_server`.
+use criterion::{black_box, BenchmarkId, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_server::arena::ArenaStorage;
+use ticket_server::clock::ManualClock;
+use ticket_server::data::{Priority, Status, Ticket, TicketDraft};
+use ticket_server::storage::{MapStorage, Storage};
+use ticket_server::store::{TicketId, TicketStore};
+
+// Keeps track of how many bytes are currently allocated.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+fn ticket(id: TicketId) -> Ticket {
+    let now = SystemTime::UNIX_EPOCH;
+    Ticket {
+        id,
+        title: ticket_title(),
+        description: ticket_description(),
+        status: Status::ToDo,
+        priority: Priority::default(),
+        due_date: None,
+        assignee: None,
+        created_at: now,
+        updated_at: now,
+        status_changes: Vec::new(),
+    }
+}
+
+fn store<S: Storage + Default + 'static>(n: usize) -> (TicketStore, Vec<TicketId>) {
+    let mut store = TicketStore::with_clock(ManualClock::default()).with_storage(S::default());
+    let ids = (0..n).map(|_| store.add_ticket(draft())).collect();
+    (store, ids)
+}
+
+// Just the storage, without the work queue and the rest of the store.
+fn storage<S: Storage + Default>(n: usize) -> S {
+    let mut storage = S::default();
+    for _ in 0..n {
+        storage.insert(ticket(storage.next_id()));
+    }
+    storage
+}
+
+// Bytes retained by whatever `build` returns.
+fn retained<T>(build: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = build();
+    (value, ALLOCATED.load(Ordering::Relaxed) - before)
+}
+
+fn report_memory() {
+    println!("Memory retained (bytes per ticket):");
+    for n in SIZES {
+        let ((_store, ids), map) = retained(|| store::<MapStorage>(n));
+        let ids_size = ids.capacity() * size_of::<TicketId>();
+        let map = map - ids_size;
+        let ((_store, _), arena) = retained(|| store::<ArenaStorage>(n));
+        let arena = arena - ids_size;
+        println!(
+            "  {n:>7} tickets, by the store:   map {:>4} | arena {:>4}",
+            map / n,
+            arena / n
+        );
+        // The tickets themselves, packed in a `Vec`: what any storage has to pay for.
+        let (_tickets, tickets) = retained(|| ids.iter().map(|id| ticket(*id)).collect::<Vec<_>>());
+        let (_map, map) = retained(|| storage::<MapStorage>(n));
+        let (_arena, arena) = retained(|| storage::<ArenaStorage>(n));
+        println!(
+            "  {n:>7} tickets, by the storage: map {:>4} | arena {:>4} | tickets alone {:>4}",
+            map / n,
+            arena / n,
+            tickets / n
+        );
+    }
+}
+
+fn lookups(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    for n in SIZES {
+        let (map, ids) = store::<MapStorage>(n);
+        let (arena, _) = store::<ArenaStorage>(n);
+        for (name, store) in [("map", map), ("arena", arena)] {
+            group.bench_with_input(BenchmarkId::new(name, n), &ids, |b, ids| {
+                b.iter(|| {
+                    for id in ids {
+                        let ticket = store.get(*id).unwrap();
+                        black_box(ticket.lock().unwrap().status);
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn main() {
+    report_memory();
+    let mut criterion = Criterion::default().configure_from_args();
+    lookups(&mut criterion);
+    criterion.final_summary();
+}
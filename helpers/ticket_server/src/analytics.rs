@@ -0,0 +1,220 @@
+// Flow metrics computed from the status transitions recorded on each ticket.
+// Everything in here works on a point-in-time copy of the tickets
+// (see `TicketStore::snapshot`), so it never holds a lock on the store.
+use crate::data::{Status, Ticket};
+use crate::store::TicketId;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+pub const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlowTimes {
+    pub id: TicketId,
+    // From creation to completion.
+    pub lead_time: Option<Duration>,
+    // From the first time work started to completion.
+    pub cycle_time: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Point {
+    pub at: SystemTime,
+    pub value: usize,
+}
+
+// When the ticket was last moved to `Done`, if it's done.
+pub fn completed_at(ticket: &Ticket) -> Option<SystemTime> {
+    if ticket.status != Status::Done {
+        return None;
+    }
+    ticket
+        .status_changes
+        .iter()
+        .rev()
+        .find(|change| change.to == Status::Done)
+        .map(|change| change.at)
+}
+
+// When work on the ticket first started, if it ever did.
+pub fn started_at(ticket: &Ticket) -> Option<SystemTime> {
+    ticket
+        .status_changes
+        .iter()
+        .find(|change| change.to == Status::InProgress)
+        .map(|change| change.at)
+}
+
+pub fn lead_time(ticket: &Ticket) -> Option<Duration> {
+    let done = completed_at(ticket)?;
+    done.duration_since(ticket.created_at).ok()
+}
+
+pub fn cycle_time(ticket: &Ticket) -> Option<Duration> {
+    let done = completed_at(ticket)?;
+    let started = started_at(ticket)?;
+    done.duration_since(started).ok()
+}
+
+pub fn flow_times(tickets: &[Ticket]) -> Vec<FlowTimes> {
+    tickets
+        .iter()
+        .map(|ticket| FlowTimes {
+            id: ticket.id,
+            lead_time: lead_time(ticket),
+            cycle_time: cycle_time(ticket),
+        })
+        .collect()
+}
+
+// The status the ticket had at the given instant,
+// or `None` if it didn't exist yet.
+pub fn status_at(ticket: &Ticket, at: SystemTime) -> Option<Status> {
+    if at < ticket.created_at {
+        return None;
+    }
+    let mut status = Status::ToDo;
+    for change in &ticket.status_changes {
+        if change.at > at {
+            break;
+        }
+        status = change.to;
+    }
+    Some(status)
+}
+
+// `weeks` consecutive points, starting at `from`.
+// Each point counts the tickets completed in `[at, at + WEEK)`.
+pub fn weekly_throughput(tickets: &[Ticket], from: SystemTime, weeks: usize) -> Vec<Point> {
+    (0..weeks)
+        .map(|week| {
+            let start = from + WEEK * week as u32;
+            let end = start + WEEK;
+            let value = tickets
+                .iter()
+                .filter_map(completed_at)
+                .filter(|done| *done >= start && *done < end)
+                .count();
+            Point { at: start, value }
+        })
+        .collect()
+}
+
+// Number of tickets in progress at each of the given instants.
+pub fn work_in_progress(tickets: &[Ticket], samples: &[SystemTime]) -> Vec<Point> {
+    samples
+        .iter()
+        .map(|&at| Point {
+            at,
+            value: tickets
+                .iter()
+                .filter(|ticket| status_at(ticket, at) == Some(Status::InProgress))
+                .count(),
+        })
+        .collect()
+}
+
+// Number of tickets in `scope` that existed but weren't done yet at each of the given instants.
+pub fn burndown(tickets: &[Ticket], scope: &[TicketId], samples: &[SystemTime]) -> Vec<Point> {
+    let tickets: Vec<&Ticket> = tickets
+        .iter()
+        .filter(|ticket| scope.contains(&ticket.id))
+        .collect();
+    samples
+        .iter()
+        .map(|&at| Point {
+            at,
+            value: tickets
+                .iter()
+                .filter(|ticket| {
+                    matches!(
+                        status_at(ticket, at),
+                        Some(Status::ToDo | Status::InProgress)
+                    )
+                })
+                .count(),
+        })
+        .collect()
+}
+
+// `count` instants, `step` apart, starting at `from`.
+pub fn sample_points(from: SystemTime, step: Duration, count: usize) -> Vec<SystemTime> {
+    (0..count).map(|i| from + step * i as u32).collect()
+}
+
+pub fn render_flow_times(times: &[FlowTimes]) -> String {
+    let rows = times
+        .iter()
+        .map(|t| {
+            vec![
+                format!("{:?}", t.id),
+                t.lead_time.map(format_duration).unwrap_or("-".into()),
+                t.cycle_time.map(format_duration).unwrap_or("-".into()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render_table(&["Ticket", "Lead time", "Cycle time"], &rows)
+}
+
+pub fn render_series(label: &str, points: &[Point]) -> String {
+    let rows = points
+        .iter()
+        .map(|p| vec![format_date(p.at), p.value.to_string()])
+        .collect::<Vec<_>>();
+    render_table(&["Date", label], &rows)
+}
+
+fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let header: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let mut out = String::new();
+    for row in [&header, &separator].into_iter().chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+    out
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+// `YYYY-MM-DD`, in UTC.
+fn format_date(at: SystemTime) -> String {
+    let days = at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / (24 * 60 * 60);
+    // Days since the epoch to a civil date, from Howard Hinnant's `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
@@ -0,0 +1,557 @@
+// A compact alternative to `MapStorage`'s layout.
+// `MapStorage` gives every ticket its own allocation, reached through its own `BTreeMap` node;
+// `ArenaStorage` keeps tickets inline in fixed-size chunks, found through a single `Vec`
+// addressed by generational indices. Handles point straight into the chunks.
+//
+// A `TicketId` handed out by the arena storage encodes both the slot and its generation:
+// when a ticket is deleted its slot can be reused, but the generation is bumped,
+// so stale ids are detected rather than silently pointing at a different ticket.
+use crate::data::Ticket;
+use crate::storage::{Cell, Storage, TicketCell};
+use crate::store::TicketId;
+use crate::sync;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, PoisonError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Index {
+    slot: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Debug)]
+enum Slot<T> {
+    Occupied { generation: u32, value: T },
+    Vacant { generation: u32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> Index {
+        self.insert_with(|_| value)
+    }
+
+    // Like `insert`, but the value can depend on the index it's going to be stored at.
+    pub fn insert_with<F>(&mut self, f: F) -> Index
+    where
+        F: FnOnce(Index) -> T,
+    {
+        let index = self.next_index();
+        let value = Slot::Occupied {
+            generation: index.generation,
+            value: f(index),
+        };
+        match self.free.last() {
+            Some(slot) if *slot == index.slot => {
+                self.free.pop();
+                self.slots[index.slot as usize] = value;
+            }
+            _ => self.slots.push(value),
+        }
+        // Only once the slot is actually filled: `f` may panic.
+        self.len += 1;
+        index
+    }
+
+    // The index the next inserted value will be stored at.
+    pub fn next_index(&self) -> Index {
+        match self.free.last() {
+            Some(&slot) => {
+                let Slot::Vacant { generation } = self.slots[slot as usize] else {
+                    unreachable!("Free slots are always vacant")
+                };
+                Index { slot, generation }
+            }
+            None => Index {
+                slot: u32::try_from(self.slots.len()).expect("The arena is full"),
+                generation: 0,
+            },
+        }
+    }
+
+    pub fn get(&self, index: Index) -> Option<&T> {
+        match self.slots.get(index.slot as usize)? {
+            Slot::Occupied { generation, value } if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, index: Index) -> Option<&mut T> {
+        match self.slots.get_mut(index.slot as usize)? {
+            Slot::Occupied { generation, value } if *generation == index.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn remove(&mut self, index: Index) -> Option<T> {
+        self.get(index)?;
+        let vacant = Slot::Vacant {
+            generation: index.generation.wrapping_add(1),
+        };
+        let Slot::Occupied { value, .. } =
+            std::mem::replace(&mut self.slots[index.slot as usize], vacant)
+        else {
+            unreachable!("We just checked that the slot is occupied")
+        };
+        self.free.push(index.slot);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Same layout, with `f` applied to every value: indices keep pointing at the same values.
+    pub fn map<U, F>(&self, mut f: F) -> Arena<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        let slots = self
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                Slot::Occupied { generation, value } => Slot::Occupied {
+                    generation: *generation,
+                    value: f(value),
+                },
+                Slot::Vacant { generation } => Slot::Vacant {
+                    generation: *generation,
+                },
+            })
+            .collect();
+        Arena {
+            slots,
+            free: self.free.clone(),
+            len: self.len,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Index, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, entry)| match entry {
+                Slot::Occupied { generation, value } => Some((
+                    Index {
+                        slot: slot as u32,
+                        generation: *generation,
+                    },
+                    value,
+                )),
+                Slot::Vacant { .. } => None,
+            })
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Index> for u64 {
+    fn from(index: Index) -> Self {
+        (index.generation as u64) << 32 | index.slot as u64
+    }
+}
+
+impl From<u64> for Index {
+    fn from(index: u64) -> Self {
+        Index {
+            slot: index as u32,
+            generation: (index >> 32) as u32,
+        }
+    }
+}
+
+// Tickets are kept `CHUNK` at a time: a chunk never moves, so handles can point into it.
+const CHUNK: usize = 64;
+
+type Chunk = [Entry; CHUNK];
+
+// A ticket, inline in its chunk. A storage only reuses an entry for another ticket
+// once no handle, and no other storage sharing the chunk, points at it anymore.
+#[derive(Default)]
+struct Entry {
+    places: AtomicUsize,
+    ticket: OnceLock<sync::Mutex<Ticket>>,
+}
+
+// Points at an entry in a chunk, keeping the chunk alive: what handles share.
+pub(crate) struct Place {
+    chunk: Arc<Chunk>,
+    offset: u32,
+}
+
+impl Place {
+    fn new(chunk: Arc<Chunk>, offset: usize) -> Self {
+        chunk[offset].places.fetch_add(1, Ordering::Relaxed);
+        Self {
+            chunk,
+            offset: offset as u32,
+        }
+    }
+
+    pub(crate) fn mutex(&self) -> &sync::Mutex<Ticket> {
+        self.chunk[self.offset as usize].mutex()
+    }
+}
+
+impl Clone for Place {
+    fn clone(&self) -> Self {
+        Self::new(self.chunk.clone(), self.offset as usize)
+    }
+}
+
+impl Drop for Place {
+    fn drop(&mut self) {
+        self.chunk[self.offset as usize]
+            .places
+            .fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl Entry {
+    fn mutex(&self) -> &sync::Mutex<Ticket> {
+        self.ticket
+            .get()
+            .expect("Only entries that were filled are pointed at")
+    }
+
+    // Replaces whatever the entry held, even a ticket a panicking editor left behind.
+    fn fill(&self, ticket: Ticket) {
+        match self.ticket.get() {
+            Some(mutex) => {
+                *mutex.lock().unwrap_or_else(PoisonError::into_inner) = ticket;
+                sync::clear_poison(mutex);
+            }
+            None => {
+                let _ = self.ticket.set(sync::Mutex::new(ticket));
+            }
+        }
+    }
+}
+
+// Tickets inline in chunks, found through an `Arena` of entry numbers: entry `n` is
+// at `n % CHUNK` in the chunk numbered `n / CHUNK`. Ids are offset by `first_id`,
+// so that several arenas can hand out ids from disjoint ranges.
+//
+// Every entry the storage holds on to, in `tickets` or in `spare`, counts as a place
+// pointing at it: see `Entry`.
+pub struct ArenaStorage {
+    tickets: Arena<u32>,
+    chunks: Vec<Arc<Chunk>>,
+    // Chunk numbers, by address: to find the entries of tickets we adopt.
+    numbers: HashMap<usize, u32>,
+    // Entries of tickets that were removed or replaced, to be reused once nothing else points at them.
+    spare: Vec<u32>,
+    // How many entries of the last chunk are taken, if we're the one filling it.
+    // Storages sharing our tickets never fill it: they start chunks of their own.
+    tail: Option<usize>,
+    first_id: u64,
+}
+
+impl ArenaStorage {
+    fn id(&self, index: Index) -> TicketId {
+        TicketId(self.first_id + u64::from(index))
+    }
+
+    // `None` if the id can't have come from this storage.
+    fn index(&self, id: TicketId) -> Option<Index> {
+        id.0.checked_sub(self.first_id).map(Index::from)
+    }
+
+    fn entry(&self, number: u32) -> &Entry {
+        let number = number as usize;
+        &self.chunks[number / CHUNK][number % CHUNK]
+    }
+
+    fn place(&self, number: u32) -> Place {
+        let number = number as usize;
+        Place::new(self.chunks[number / CHUNK].clone(), number % CHUNK)
+    }
+
+    // The number of the chunk, adding it to ours if needed.
+    fn number(&mut self, chunk: &Arc<Chunk>) -> u32 {
+        let address = Arc::as_ptr(chunk) as usize;
+        if let Some(number) = self.numbers.get(&address) {
+            return *number;
+        }
+        let number = u32::try_from(self.chunks.len()).expect("Too many chunks");
+        self.chunks.push(chunk.clone());
+        self.numbers.insert(address, number);
+        number
+    }
+
+    // Finds an entry for the ticket, and holds on to it: a spare one if we can, a new one otherwise.
+    fn hold(&mut self, ticket: Ticket) -> u32 {
+        let unshared = |number: &u32| self.entry(*number).places.load(Ordering::Acquire) == 1;
+        if let Some(spare) = self.spare.iter().position(unshared) {
+            let number = self.spare.swap_remove(spare);
+            self.entry(number).fill(ticket);
+            return number;
+        }
+        let taken = match self.tail {
+            Some(taken) if taken < CHUNK => taken,
+            _ => {
+                let chunk = Arc::new(std::array::from_fn(|_| Entry::default()));
+                self.number(&chunk);
+                0
+            }
+        };
+        self.tail = Some(taken + 1);
+        let number = ((self.chunks.len() - 1) * CHUNK + taken) as u32;
+        let entry = self.entry(number);
+        entry.places.fetch_add(1, Ordering::Relaxed);
+        entry.fill(ticket);
+        number
+    }
+}
+
+impl Default for ArenaStorage {
+    fn default() -> Self {
+        Self::starting_at(0)
+    }
+}
+
+impl Storage for ArenaStorage {
+    fn starting_at(first_id: u64) -> Self {
+        Self {
+            tickets: Arena::new(),
+            chunks: Vec::new(),
+            numbers: HashMap::new(),
+            spare: Vec::new(),
+            tail: None,
+            first_id,
+        }
+    }
+
+    fn next_id(&self) -> TicketId {
+        self.id(self.tickets.next_index())
+    }
+
+    fn insert(&mut self, ticket: Ticket) {
+        debug_assert_eq!(ticket.id, self.next_id());
+        let number = self.hold(ticket);
+        self.tickets.insert(number);
+    }
+
+    fn get(&self, id: TicketId) -> Option<&sync::Mutex<Ticket>> {
+        let number = self.tickets.get(self.index(id)?)?;
+        Some(self.entry(*number).mutex())
+    }
+
+    fn cell(&self, id: TicketId) -> Option<TicketCell> {
+        let number = self.tickets.get(self.index(id)?)?;
+        Some(TicketCell(Cell::Arena(self.place(*number))))
+    }
+
+    fn adopt(&mut self, id: TicketId, cell: TicketCell) {
+        let Cell::Arena(place) = cell.0 else {
+            unreachable!("An arena storage only adopts tickets from another arena")
+        };
+        let Some(index) = self
+            .index(id)
+            .filter(|index| self.tickets.get(*index).is_some())
+        else {
+            return;
+        };
+        let chunk = self.number(&place.chunk) as usize;
+        let number = (chunk * CHUNK + place.offset as usize) as u32;
+        self.entry(number).places.fetch_add(1, Ordering::Relaxed);
+        let replaced = std::mem::replace(self.tickets.get_mut(index).unwrap(), number);
+        self.spare.push(replaced);
+    }
+
+    fn remove(&mut self, id: TicketId) -> Option<TicketCell> {
+        let number = self.tickets.remove(self.index(id)?)?;
+        self.spare.push(number);
+        Some(TicketCell(Cell::Arena(self.place(number))))
+    }
+
+    // Slots are reused, so storage order isn't id order.
+    fn ids(&self) -> Vec<TicketId> {
+        let mut ids: Vec<_> = self
+            .tickets
+            .iter()
+            .map(|(index, _)| self.id(index))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn share(&self) -> Box<dyn Storage> {
+        for (_, number) in self.tickets.iter() {
+            self.entry(*number).places.fetch_add(1, Ordering::Relaxed);
+        }
+        Box::new(Self {
+            tickets: self.tickets.clone(),
+            chunks: self.chunks.clone(),
+            numbers: self.numbers.clone(),
+            spare: Vec::new(),
+            tail: None,
+            first_id: self.first_id,
+        })
+    }
+
+    fn copy(&self) -> Box<dyn Storage> {
+        let mut copy = Self::starting_at(self.first_id);
+        let tickets = self.tickets.map(|number| {
+            let ticket = self.entry(*number).mutex().lock().unwrap().clone();
+            copy.hold(ticket)
+        });
+        copy.tickets = tickets;
+        Box::new(copy)
+    }
+}
+
+impl Drop for ArenaStorage {
+    fn drop(&mut self) {
+        let held = self.tickets.iter().map(|(_, number)| number);
+        for number in held.chain(&self.spare) {
+            self.entry(*number).places.fetch_sub(1, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn stale_indices_are_detected() {
+        let mut arena = Arena::new();
+        let first = arena.insert("first");
+        assert_eq!(arena.remove(first), Some("first"));
+
+        // The slot is reused, with a new generation.
+        let second = arena.insert("second");
+        assert_eq!(first.slot, second.slot);
+        assert_ne!(first, second);
+
+        assert_eq!(arena.get(first), None);
+        assert_eq!(arena.get_mut(first), None);
+        assert_eq!(arena.remove(first), None);
+        assert_eq!(arena.get(second), Some(&"second"));
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    fn ticket_ids_round_trip() {
+        let storage = ArenaStorage::starting_at(100);
+        let index = Index {
+            slot: 7,
+            generation: 3,
+        };
+        assert_eq!(storage.index(storage.id(index)), Some(index));
+        assert_eq!(storage.index(TicketId(99)), None);
+    }
+
+    fn add(storage: &mut dyn Storage) -> TicketId {
+        let draft = crate::data::TicketDraft {
+            title: ticket_fields::test_helpers::ticket_title(),
+            description: ticket_fields::test_helpers::ticket_description(),
+        };
+        let id = storage.next_id();
+        storage.insert(crate::store::new_ticket(id, draft, SystemTime::UNIX_EPOCH));
+        id
+    }
+
+    // The entry the storage keeps the ticket in.
+    fn entry(storage: &ArenaStorage, id: TicketId) -> u32 {
+        *storage.tickets.get(storage.index(id).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn entries_are_reused_once_nothing_points_at_them() {
+        let mut storage = ArenaStorage::default();
+        let deleted = add(&mut storage);
+        let handle = storage.remove(deleted).unwrap();
+
+        // The handle still points at the deleted ticket: its entry can't be reused yet.
+        let kept = add(&mut storage);
+        assert_eq!(entry(&storage, kept), 1);
+        assert_eq!(handle.mutex().lock().unwrap().id, deleted);
+
+        drop(handle);
+        let reused = add(&mut storage);
+        assert_eq!(entry(&storage, reused), 0);
+        assert_eq!(storage.get(reused).unwrap().lock().unwrap().id, reused);
+        assert_eq!(storage.get(kept).unwrap().lock().unwrap().id, kept);
+    }
+
+    #[test]
+    fn shared_storages_fill_chunks_of_their_own() {
+        let mut storage = ArenaStorage::default();
+        let shared = add(&mut storage);
+        let mut other = storage.share();
+
+        // Both hand out the same id, for different tickets.
+        let id = add(&mut storage);
+        assert_eq!(add(&mut *other), id);
+        other.get(id).unwrap().lock().unwrap().assignee = Some("Bob".into());
+        assert_eq!(storage.get(id).unwrap().lock().unwrap().assignee, None);
+        // The ticket they had before is still the same.
+        other.get(shared).unwrap().lock().unwrap().assignee = Some("Alice".into());
+        let assignee = storage
+            .get(shared)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .assignee
+            .clone();
+        assert_eq!(assignee.as_deref(), Some("Alice"));
+
+        // Its entry is only reused once neither of them holds on to it.
+        storage.remove(shared);
+        let fresh = add(&mut storage);
+        assert_eq!(entry(&storage, fresh), 2);
+        other.remove(shared);
+        drop(other);
+        let reused = add(&mut storage);
+        assert_eq!(entry(&storage, reused), 0);
+    }
+
+    #[test]
+    fn a_failed_insert_leaves_the_arena_as_it_was() {
+        let mut arena = Arena::new();
+        let first = arena.insert(1);
+        arena.remove(first);
+        let failed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.insert_with(|_| -> i32 { panic!("Boom") })
+        }));
+        assert!(failed.is_err());
+        assert_eq!(arena.len(), 0);
+        // The free slot is still there, for the next insert to reuse.
+        assert_eq!(arena.insert(2).slot, first.slot);
+    }
+}
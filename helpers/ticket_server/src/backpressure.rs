@@ -0,0 +1,152 @@
+// What a client does when the server's queue is full.
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::TrySendError;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use actor::ActorHandle;
+
+use crate::{ClientError, Command};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    // Give up straight away, with `ClientError::Overloaded`.
+    #[default]
+    FailFast,
+    // Wait for as long as it takes for the queue to have room.
+    Block,
+    // Wait for the queue to have room, up to the given duration.
+    BlockFor(Duration),
+    // Try again after a randomized, exponentially growing delay, until `deadline` has elapsed.
+    Retry {
+        initial_backoff: Duration,
+        max_backoff: Duration,
+        deadline: Duration,
+    },
+}
+
+// How often the queue was full, for each strategy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OverloadStats {
+    pub fail_fast: OverloadCount,
+    pub block: OverloadCount,
+    pub block_for: OverloadCount,
+    pub retry: OverloadCount,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OverloadCount {
+    // Requests that found the queue full on their first attempt.
+    pub overloaded: u64,
+    // Requests that eventually failed with `ClientError::Overloaded`.
+    pub rejected: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters([AtomicCount; 4]);
+
+#[derive(Debug, Default)]
+struct AtomicCount {
+    overloaded: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn snapshot(&self) -> OverloadStats {
+        let [fail_fast, block, block_for, retry] = self.0.each_ref().map(|count| OverloadCount {
+            overloaded: count.overloaded.load(Ordering::Relaxed),
+            rejected: count.rejected.load(Ordering::Relaxed),
+        });
+        OverloadStats {
+            fail_fast,
+            block,
+            block_for,
+            retry,
+        }
+    }
+
+    fn of(&self, strategy: Backpressure) -> &AtomicCount {
+        let index = match strategy {
+            Backpressure::FailFast => 0,
+            Backpressure::Block => 1,
+            Backpressure::BlockFor(_) => 2,
+            Backpressure::Retry { .. } => 3,
+        };
+        &self.0[index]
+    }
+}
+
+pub(crate) fn send(
+    actor: &ActorHandle<Command>,
+    command: Command,
+    strategy: Backpressure,
+    counters: &Counters,
+) -> Result<(), ClientError> {
+    let command = match actor.try_send(command) {
+        Ok(()) => return Ok(()),
+        Err(TrySendError::Disconnected(_)) => return Err(ClientError::Disconnected),
+        Err(TrySendError::Full(command)) => command,
+    };
+    let count = counters.of(strategy);
+    count.overloaded.fetch_add(1, Ordering::Relaxed);
+
+    let outcome = match strategy {
+        Backpressure::FailFast => Err(ClientError::Overloaded),
+        Backpressure::Block => actor.send(command).map_err(|_| ClientError::Disconnected),
+        Backpressure::BlockFor(timeout) => match actor.send_timeout(command, timeout) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(ClientError::Overloaded),
+            Err(TrySendError::Disconnected(_)) => Err(ClientError::Disconnected),
+        },
+        Backpressure::Retry {
+            initial_backoff,
+            max_backoff,
+            deadline,
+        } => retry(actor, command, deadline, |attempt| {
+            let backoff = initial_backoff
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(max_backoff);
+            jitter(backoff)
+        }),
+    };
+    if outcome == Err(ClientError::Overloaded) {
+        count.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+    outcome
+}
+
+fn retry<F>(
+    actor: &ActorHandle<Command>,
+    mut command: Command,
+    deadline: Duration,
+    delay: F,
+) -> Result<(), ClientError>
+where
+    F: Fn(u32) -> Duration,
+{
+    let deadline = Instant::now() + deadline;
+    for attempt in 0.. {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        sleep(delay(attempt).min(deadline - now));
+        command = match actor.try_send(command) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Disconnected(_)) => return Err(ClientError::Disconnected),
+            Err(TrySendError::Full(command)) => command,
+        };
+    }
+    Err(ClientError::Overloaded)
+}
+
+// A random duration in `[backoff / 2, backoff]`, so that clients that got rejected
+// at the same time don't all come back at the same time.
+fn jitter(backoff: Duration) -> Duration {
+    // `RandomState` is randomly seeded: good enough as a source of jitter.
+    let random = RandomState::new().hash_one(Instant::now());
+    let half = backoff / 2;
+    half + half.mul_f64(random as f64 / u64::MAX as f64)
+}
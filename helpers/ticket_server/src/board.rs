@@ -0,0 +1,163 @@
+// A kanban board on top of `TicketStore`.
+// There is one column per `Status`, and tickets within a column are kept in a manual order.
+// Status changes must go through the board (rather than through a ticket handle)
+// for columns and work-in-progress limits to stay accurate.
+use crate::data::{Status, TicketDraft, TicketPatch};
+use crate::leases::EditError;
+use crate::store::{TicketId, TicketStore};
+use std::collections::HashMap;
+
+pub const COLUMNS: [Status; 3] = [Status::ToDo, Status::InProgress, Status::Done];
+
+pub struct Board {
+    store: TicketStore,
+    columns: HashMap<Status, Vec<TicketId>>,
+    wip_limits: HashMap<Status, usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum BoardError {
+    #[error("Ticket {0:?} is not on the board")]
+    UnknownTicket(TicketId),
+    #[error("The {status:?} column is full: its work-in-progress limit is {limit}")]
+    WipLimitReached { status: Status, limit: usize },
+    #[error(transparent)]
+    Edit(#[from] EditError),
+}
+
+impl Board {
+    // Builds a board out of the tickets that are already in the store,
+    // ordered by id within each column.
+    pub fn new(store: TicketStore) -> Self {
+        let mut columns: HashMap<Status, Vec<TicketId>> =
+            COLUMNS.iter().map(|status| (*status, Vec::new())).collect();
+        for ticket in store.snapshot() {
+            columns.get_mut(&ticket.status).unwrap().push(ticket.id);
+        }
+        Self {
+            store,
+            columns,
+            wip_limits: HashMap::new(),
+        }
+    }
+
+    pub fn with_wip_limit(mut self, status: Status, limit: usize) -> Self {
+        self.wip_limits.insert(status, limit);
+        self
+    }
+
+    pub fn store(&self) -> &TicketStore {
+        &self.store
+    }
+
+    pub fn wip_limit(&self, status: Status) -> Option<usize> {
+        self.wip_limits.get(&status).copied()
+    }
+
+    // The tickets in the given column, top to bottom.
+    pub fn column(&self, status: Status) -> &[TicketId] {
+        &self.columns[&status]
+    }
+
+    // New tickets go at the bottom of the `ToDo` column.
+    // The limit on `ToDo`, if any, is enforced as well.
+    pub fn add_ticket(&mut self, draft: TicketDraft) -> Result<TicketId, BoardError> {
+        self.check_wip_limit(Status::ToDo)?;
+        let id = self.store.add_ticket(draft);
+        self.columns.get_mut(&Status::ToDo).unwrap().push(id);
+        Ok(id)
+    }
+
+    // Moves the ticket to the bottom of the `to` column.
+    pub fn move_ticket(&mut self, id: TicketId, to: Status) -> Result<(), BoardError> {
+        self.move_ticket_inner(id, to, false)
+    }
+
+    // Same as `move_ticket`, but ignores the work-in-progress limit of the `to` column.
+    pub fn force_move_ticket(&mut self, id: TicketId, to: Status) -> Result<(), BoardError> {
+        self.move_ticket_inner(id, to, true)
+    }
+
+    // Moves the ticket to the given position within its current column.
+    // Positions past the end of the column are clamped.
+    pub fn reorder(&mut self, id: TicketId, position: usize) -> Result<(), BoardError> {
+        let (status, index) = self.locate(id)?;
+        let column = self.columns.get_mut(&status).unwrap();
+        column.remove(index);
+        column.insert(position.min(column.len()), id);
+        Ok(())
+    }
+
+    // Applies the patch, moving the ticket across columns if its status changes.
+    // Nothing is applied if the move would break a work-in-progress limit.
+    pub fn update(&mut self, patch: TicketPatch) -> Result<(), BoardError> {
+        let (current, _) = self.locate(patch.id)?;
+        if let Some(status) = patch.status {
+            if status != current {
+                self.check_wip_limit(status)?;
+            }
+        }
+        let id = patch.id;
+        self.store.update(patch)?;
+        self.sync_column(id, current);
+        Ok(())
+    }
+
+    fn move_ticket_inner(
+        &mut self,
+        id: TicketId,
+        to: Status,
+        force: bool,
+    ) -> Result<(), BoardError> {
+        let (from, _) = self.locate(id)?;
+        if from == to {
+            return Ok(());
+        }
+        if !force {
+            self.check_wip_limit(to)?;
+        }
+        self.store.update(TicketPatch {
+            status: Some(to),
+            ..TicketPatch::new(id)
+        })?;
+        self.sync_column(id, from);
+        Ok(())
+    }
+
+    // Moves the ticket to the bottom of the column matching its status in the store,
+    // if it's not `previous` anymore.
+    fn sync_column(&mut self, id: TicketId, previous: Status) {
+        let Some(ticket) = self.store.get(id) else {
+            return;
+        };
+        let status = ticket.lock().unwrap().status;
+        if status != previous {
+            self.columns
+                .get_mut(&previous)
+                .unwrap()
+                .retain(|t| *t != id);
+            self.columns.get_mut(&status).unwrap().push(id);
+        }
+    }
+
+    fn check_wip_limit(&self, status: Status) -> Result<(), BoardError> {
+        match self.wip_limit(status) {
+            Some(limit) if self.columns[&status].len() >= limit => {
+                Err(BoardError::WipLimitReached { status, limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn locate(&self, id: TicketId) -> Result<(Status, usize), BoardError> {
+        COLUMNS
+            .iter()
+            .find_map(|status| {
+                self.columns[status]
+                    .iter()
+                    .position(|t| *t == id)
+                    .map(|index| (*status, index))
+            })
+            .ok_or(BoardError::UnknownTicket(id))
+    }
+}
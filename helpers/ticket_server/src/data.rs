@@ -0,0 +1,70 @@
+use crate::store::TicketId;
+use std::time::SystemTime;
+use ticket_fields::{TicketDescription, TicketTitle};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ticket {
+    pub id: TicketId,
+    pub title: TicketTitle,
+    pub description: TicketDescription,
+    pub status: Status,
+    pub priority: Priority,
+    pub due_date: Option<SystemTime>,
+    pub assignee: Option<String>,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+    // Every status transition the ticket went through, oldest first.
+    pub status_changes: Vec<StatusChange>,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub struct StatusChange {
+    pub from: Status,
+    pub to: Status,
+    pub at: SystemTime,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TicketDraft {
+    pub title: TicketTitle,
+    pub description: TicketDescription,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TicketPatch {
+    pub id: TicketId,
+    pub title: Option<TicketTitle>,
+    pub description: Option<TicketDescription>,
+    pub status: Option<Status>,
+    pub priority: Option<Priority>,
+    pub due_date: Option<SystemTime>,
+}
+
+impl TicketPatch {
+    // A patch that leaves the ticket as it is: fill in the fields you want to change.
+    pub fn new(id: TicketId) -> Self {
+        Self {
+            id,
+            title: None,
+            description: None,
+            status: None,
+            priority: None,
+            due_date: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Status {
+    ToDo,
+    InProgress,
+    Done,
+}
+
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
@@ -0,0 +1,59 @@
+// Change notifications for `TicketStore`.
+// Events are emitted once a mutation has been fully applied (and the ticket lock released),
+// so observers always see the new state if they look the ticket up.
+use crate::data::StatusChange;
+use crate::store::TicketId;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TicketEvent {
+    Created { id: TicketId },
+    FieldChanged { id: TicketId, field: Field },
+    StatusTransitioned { id: TicketId, change: StatusChange },
+    Deleted { id: TicketId },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Description,
+    Priority,
+    DueDate,
+    Assignee,
+}
+
+type Callback = Arc<dyn Fn(&TicketEvent) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct Observers {
+    callbacks: Vec<Callback>,
+    subscribers: Vec<Sender<TicketEvent>>,
+}
+
+impl Observers {
+    pub fn on_event<F>(&mut self, callback: F)
+    where
+        F: Fn(&TicketEvent) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Arc::new(callback));
+    }
+
+    // Every event emitted from now on is sent to the returned receiver.
+    // Dropping the receiver cancels the subscription.
+    pub fn subscribe(&mut self) -> Receiver<TicketEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn notify(&mut self, events: Vec<TicketEvent>) {
+        for event in events {
+            for callback in &self.callbacks {
+                callback(&event);
+            }
+            self.subscribers
+                .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// Exclusive, time-bounded edit rights on a ticket.
+//
+// A ticket handle (`Arc<Mutex<Ticket>>`) can be locked for as long as its owner likes,
+// stalling everybody else. A lease, instead, expires on its own: while it's active,
+// writes that go through the store without it are rejected straight away, with an error
+// telling who holds the ticket and until when. Once it expires, it's revoked the next time
+// someone looks at it.
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+use std::time::SystemTime;
+
+use crate::store::TicketId;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lease {
+    pub id: TicketId,
+    pub holder: String,
+    pub until: SystemTime,
+    // Tells two leases on the same ticket apart, even if they have the same holder.
+    // Remote clients send it back to prove they hold the lease: it can't be guessed.
+    pub(crate) token: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EditError {
+    #[error("There is no ticket with id {0:?}")]
+    UnknownTicket(TicketId),
+    #[error("Ticket {id:?} is leased by {holder} until {until:?}")]
+    Leased {
+        id: TicketId,
+        holder: String,
+        until: SystemTime,
+    },
+    #[error("The lease on ticket {0:?} has expired or was released")]
+    LeaseLost(TicketId),
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Leases {
+    active: BTreeMap<TicketId, Lease>,
+    granted: u64,
+    // `RandomState` is randomly seeded: hashing how many leases we granted with it
+    // gives tokens that are unique, but that nobody can work out from the ones they got.
+    // Clones hand out the same tokens, so a restored store grants replayed leases again.
+    tokens: RandomState,
+}
+
+impl Leases {
+    pub(crate) fn grant(
+        &mut self,
+        id: TicketId,
+        holder: String,
+        until: SystemTime,
+        now: SystemTime,
+    ) -> Result<Lease, EditError> {
+        self.check(id, None, now)?;
+        let lease = Lease {
+            id,
+            holder,
+            until,
+            token: self.tokens.hash_one(self.granted),
+        };
+        self.granted += 1;
+        self.active.insert(id, lease.clone());
+        Ok(lease)
+    }
+
+    pub(crate) fn extend(
+        &mut self,
+        lease: &Lease,
+        until: SystemTime,
+        now: SystemTime,
+    ) -> Result<Lease, EditError> {
+        self.check(lease.id, Some(lease), now)?;
+        let active = self
+            .active
+            .get_mut(&lease.id)
+            .ok_or(EditError::LeaseLost(lease.id))?;
+        active.until = until;
+        Ok(active.clone())
+    }
+
+    pub(crate) fn release(&mut self, lease: &Lease, now: SystemTime) -> Result<(), EditError> {
+        self.check(lease.id, Some(lease), now)?;
+        self.active
+            .remove(&lease.id)
+            .map(|_| ())
+            .ok_or(EditError::LeaseLost(lease.id))
+    }
+
+    pub(crate) fn forget(&mut self, id: TicketId) {
+        self.active.remove(&id);
+    }
+
+    // Can the ticket be written to, by whoever holds `lease` (or by anyone, if `None`)?
+    pub(crate) fn check(
+        &mut self,
+        id: TicketId,
+        lease: Option<&Lease>,
+        now: SystemTime,
+    ) -> Result<(), EditError> {
+        if self
+            .active
+            .get(&id)
+            .is_some_and(|active| active.until <= now)
+        {
+            self.active.remove(&id);
+        }
+        match (self.active.get(&id), lease) {
+            (None, None) => Ok(()),
+            (Some(active), Some(lease)) if active.token == lease.token => Ok(()),
+            (Some(active), _) => Err(EditError::Leased {
+                id,
+                holder: active.holder.clone(),
+                until: active.until,
+            }),
+            (None, Some(lease)) => Err(EditError::LeaseLost(lease.id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_cannot_be_guessed_from_other_leases() {
+        let mut leases = Leases::default();
+        let now = SystemTime::UNIX_EPOCH;
+        let until = now + std::time::Duration::from_secs(60);
+        let first = leases
+            .grant(TicketId(0), "alice".into(), until, now)
+            .unwrap();
+        let second = leases.grant(TicketId(1), "bob".into(), until, now).unwrap();
+        assert_ne!(second.token, first.token.wrapping_add(1));
+
+        let forged = Lease {
+            id: TicketId(1),
+            token: first.token.wrapping_add(1),
+            ..first
+        };
+        assert!(leases.check(TicketId(1), Some(&forged), now).is_err());
+        assert!(leases.check(TicketId(1), Some(&second), now).is_ok());
+    }
+}
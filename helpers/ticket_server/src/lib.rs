@@ -0,0 +1,737 @@
+// A ticket store served by an actor thread.
+//
+// Clients talk to the server through a bounded mailbox: the server schedules reads and
+// writes, restarts itself if a command panics and persists the store on shutdown.
+// The same server can be partitioned across several threads or exposed over a Unix socket.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actor::{ActorHandle, Pending, Reply, WaitError};
+
+use crate::backpressure::{Backpressure, Counters, OverloadStats};
+use crate::clock::{Clock, SystemClock};
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::events::TicketEvent;
+use crate::leases::{EditError, Lease};
+use crate::locking::LockSet;
+use crate::metrics::{CommandKind, ErrorKind, Metrics, MetricsSnapshot};
+use crate::partitioned::{PartitionedHandle, Partitions};
+use crate::scheduling::{command_is_read, Lanes, SchedulingPolicy};
+use crate::server::{Server, SupervisorStatus};
+use crate::shutdown::{Persistence, ServerHandle};
+use crate::storage::{MapStorage, Storage};
+use crate::store::{TicketHandle, TicketId, TicketStore};
+use crate::trace::RequestContext;
+
+pub mod analytics;
+pub mod arena;
+pub mod backpressure;
+pub mod board;
+pub mod clock;
+pub mod data;
+pub mod events;
+pub mod leases;
+pub mod locking;
+pub mod metrics;
+pub mod partitioned;
+pub mod query;
+pub mod queue;
+#[cfg(unix)]
+pub mod remote;
+pub mod scheduling;
+pub mod server;
+pub mod shutdown;
+pub mod storage;
+pub mod store;
+mod sync;
+pub mod trace;
+#[cfg(unix)]
+mod wire;
+
+#[derive(Clone)]
+pub struct TicketStoreClient {
+    // Where commands are sent. Reads get a lane of their own, unless the server handles
+    // commands in arrival order: both handles then point to the same lane.
+    reads: ActorHandle<Command>,
+    writes: ActorHandle<Command>,
+    timeout: Duration,
+    backpressure: Backpressure,
+    // Shared by all the clones of a client.
+    counters: Arc<Counters>,
+    // Flipped to `false` when the server starts shutting down.
+    accepting: Arc<AtomicBool>,
+    // Shared with the server.
+    metrics: Arc<Metrics>,
+}
+
+impl TicketStoreClient {
+    // How long to wait for the server to respond, before giving up.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    // What to do when the server's queue is full. Defaults to `Backpressure::FailFast`.
+    pub fn with_backpressure(self, backpressure: Backpressure) -> Self {
+        Self {
+            backpressure,
+            ..self
+        }
+    }
+
+    // How often this client (and its clones) found the server's queue full.
+    pub fn overload_stats(&self) -> OverloadStats {
+        self.counters.snapshot()
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot(self.overload_stats())
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, ClientError> {
+        self.request(|response_channel| Command::Insert {
+            draft,
+            response_channel,
+        })
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<TicketHandle>, ClientError> {
+        self.request(|response_channel| Command::Get {
+            id,
+            response_channel,
+        })
+    }
+
+    // Batches are processed in one round trip: prefer them to a loop
+    // when you have many operations to perform.
+    // Tickets are inserted in order, and their ids are returned in the same order.
+    pub fn insert_many(&self, drafts: Vec<TicketDraft>) -> Result<Vec<TicketId>, ClientError> {
+        self.request(|response_channel| Command::InsertMany {
+            drafts,
+            response_channel,
+        })
+    }
+
+    // One entry per id, in the same order.
+    pub fn get_many(&self, ids: Vec<TicketId>) -> Result<Vec<Option<TicketHandle>>, ClientError> {
+        self.request(|response_channel| Command::GetMany {
+            ids,
+            response_channel,
+        })
+    }
+
+    // Handles on all the given tickets, to be locked together: see `LockSet`.
+    pub fn lock_set(&self, ids: Vec<TicketId>) -> Result<LockSet, ClientError> {
+        let tickets = self.get_many(ids.clone())?;
+        let tickets = ids
+            .into_iter()
+            .zip(tickets)
+            .map(|(id, ticket)| {
+                ticket
+                    .map(|ticket| (id, ticket))
+                    .ok_or(EditError::UnknownTicket(id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LockSet::new(tickets))
+    }
+
+    // Patches are applied in order. One result per patch, in the same order:
+    // a patch targeting a ticket that doesn't exist doesn't stop the ones after it.
+    pub fn patch_many(
+        &self,
+        patches: Vec<TicketPatch>,
+    ) -> Result<Vec<Result<(), EditError>>, ClientError> {
+        self.request(|response_channel| Command::PatchMany {
+            patches,
+            response_channel,
+        })
+    }
+
+    pub fn peek_next(&self) -> Result<Option<TicketId>, ClientError> {
+        self.request(|response_channel| Command::PeekNext { response_channel })
+    }
+
+    // Claims are processed one at a time by the server,
+    // so concurrent callers never get the same ticket.
+    // Tickets someone holds a lease on are passed over.
+    pub fn claim_next(&self, assignee: String) -> Result<Option<TicketId>, ClientError> {
+        self.request(|response_channel| Command::ClaimNext {
+            assignee,
+            response_channel,
+        })
+    }
+
+    // Fails with `EditError::UnknownTicket` if there's no such ticket,
+    // or with `EditError::Leased` if someone else holds a lease on it.
+    pub fn update(&self, patch: TicketPatch) -> Result<(), ClientError> {
+        let outcome = self.request(|response_channel| Command::Update {
+            patch,
+            response_channel,
+        })?;
+        Ok(outcome?)
+    }
+
+    // Exclusive edit rights on the ticket, for `duration`: see `TicketStore::checkout`.
+    pub fn checkout(
+        &self,
+        id: TicketId,
+        holder: String,
+        duration: Duration,
+    ) -> Result<Lease, ClientError> {
+        let outcome = self.request(|response_channel| Command::Checkout {
+            id,
+            holder,
+            duration,
+            response_channel,
+        })?;
+        Ok(outcome?)
+    }
+
+    pub fn renew(&self, lease: &Lease, duration: Duration) -> Result<Lease, ClientError> {
+        let outcome = self.request(|response_channel| Command::Renew {
+            lease: lease.clone(),
+            duration,
+            response_channel,
+        })?;
+        Ok(outcome?)
+    }
+
+    pub fn commit(&self, lease: Lease, patch: TicketPatch) -> Result<(), ClientError> {
+        let outcome = self.request(|response_channel| Command::Commit {
+            lease,
+            patch,
+            response_channel,
+        })?;
+        Ok(outcome?)
+    }
+
+    pub fn abandon(&self, lease: Lease) -> Result<(), ClientError> {
+        let outcome = self.request(|response_channel| Command::Abandon {
+            lease,
+            response_channel,
+        })?;
+        Ok(outcome?)
+    }
+
+    // Fails with `EditError::Leased` if someone holds a lease on the ticket.
+    pub fn delete(&self, id: TicketId) -> Result<Option<TicketHandle>, ClientError> {
+        let outcome = self.request(|response_channel| Command::Delete {
+            id,
+            response_channel,
+        })?;
+        Ok(outcome?)
+    }
+
+    // A copy of every ticket, ordered by id.
+    pub fn list(&self) -> Result<Vec<Ticket>, ClientError> {
+        self.request(|response_channel| Command::List { response_channel })
+    }
+
+    // Streams every change applied to the store from now on.
+    // Drop the receiver to unsubscribe.
+    pub fn subscribe(&self) -> Result<Receiver<TicketEvent>, ClientError> {
+        self.request(|response_channel| Command::Subscribe { response_channel })
+    }
+
+    fn request<T, F>(&self, command: F) -> Result<T, ClientError>
+    where
+        F: FnOnce(Responder<T>) -> Command,
+    {
+        let span = trace::client_span();
+        let _entered = span.enter();
+        let start = Instant::now();
+        let response = self.submit(command).and_then(|pending| self.wait(pending));
+        self.record(start, response.as_ref().err());
+        response
+    }
+
+    // Times a request sent at `start`, and counts it as failed if it did.
+    fn record(&self, start: Instant, error: Option<&ClientError>) {
+        let error = error.map(ClientError::kind);
+        self.metrics.responded(start.elapsed(), error);
+    }
+
+    // Queues the command without waiting for the server to process it.
+    // The command gets a new correlation id, which is recorded on the current span.
+    fn submit<T, F>(&self, command: F) -> Result<Pending<T>, ClientError>
+    where
+        F: FnOnce(Responder<T>) -> Command,
+    {
+        if !self.accepting.load(Ordering::Acquire) {
+            return Err(ClientError::ShuttingDown);
+        }
+        let (reply, pending) = actor::oneshot();
+        let context = RequestContext::new();
+        let command = command(Responder { reply, context });
+        if let Some(kind) = command.kind() {
+            tracing::Span::current().record("command", kind.name());
+        }
+        let lane = match command_is_read(&command) {
+            true => &self.reads,
+            false => &self.writes,
+        };
+        backpressure::send(lane, command, self.backpressure, &self.counters)?;
+        self.metrics.enqueued();
+        Ok(pending)
+    }
+
+    fn wait<T>(&self, pending: Pending<T>) -> Result<T, ClientError> {
+        pending.wait_timeout(self.timeout).map_err(|e| match e {
+            WaitError::TimedOut(timeout) => ClientError::TimedOut(timeout),
+            // A worker panicked on our command. Without a supervisor, every command
+            // still queued behind it is dropped along with the mailbox, too.
+            WaitError::Dropped => ClientError::RequestDropped,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ClientError {
+    #[error("The store is overloaded")]
+    Overloaded,
+    #[error("The store server is not running")]
+    Disconnected,
+    #[error("The store server is shutting down")]
+    ShuttingDown,
+    #[error("The store server dropped the request without responding")]
+    RequestDropped,
+    #[error("The store server didn't respond within {0:?}")]
+    TimedOut(Duration),
+    // The server processed the request, but refused to apply it.
+    #[error(transparent)]
+    Edit(#[from] EditError),
+}
+
+impl ClientError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ClientError::Overloaded => ErrorKind::Overloaded,
+            ClientError::Disconnected => ErrorKind::Disconnected,
+            ClientError::ShuttingDown => ErrorKind::ShuttingDown,
+            ClientError::RequestDropped => ErrorKind::RequestDropped,
+            ClientError::TimedOut(_) => ErrorKind::TimedOut,
+            ClientError::Edit(_) => ErrorKind::Edit,
+        }
+    }
+}
+
+pub fn launch(capacity: usize) -> ServerHandle {
+    ServerBuilder::new(capacity).launch()
+}
+
+// Same as `launch`, but the server timestamps tickets using the given clock.
+pub fn launch_with_clock<C: Clock + 'static>(capacity: usize, clock: C) -> ServerHandle {
+    ServerBuilder::new(capacity).clock(clock).launch()
+}
+
+pub struct ServerBuilder {
+    capacity: usize,
+    clock: Arc<dyn Clock>,
+    persistence: Option<Box<dyn Persistence>>,
+    checkpoint_every: Option<usize>,
+    scheduling: SchedulingPolicy,
+    starvation_limit: usize,
+    lookahead: usize,
+    // Builds a worker's storage, given the first id it hands out.
+    storage: fn(u64) -> Box<dyn Storage>,
+}
+
+impl ServerBuilder {
+    pub const DEFAULT_STARVATION_LIMIT: usize = 32;
+    pub const DEFAULT_LOOKAHEAD: usize = 8;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clock: Arc::new(SystemClock),
+            persistence: None,
+            checkpoint_every: None,
+            scheduling: SchedulingPolicy::default(),
+            starvation_limit: Self::DEFAULT_STARVATION_LIMIT,
+            lookahead: Self::DEFAULT_LOOKAHEAD,
+            storage: |first_id| Box::new(MapStorage::starting_at(first_id)),
+        }
+    }
+
+    // Where the server keeps its tickets: a `MapStorage` by default.
+    pub fn storage<S: Storage + 'static>(self) -> Self {
+        Self {
+            storage: |first_id| Box::new(S::starting_at(first_id)),
+            ..self
+        }
+    }
+
+    pub fn clock<C: Clock + 'static>(self, clock: C) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            ..self
+        }
+    }
+
+    // Invoked with the final state of the store when the server shuts down.
+    pub fn persistence<P: Persistence + 'static>(self, persistence: P) -> Self {
+        Self {
+            persistence: Some(Box::new(persistence)),
+            ..self
+        }
+    }
+
+    // Restart the server, rather than letting it die, if it panics while processing a command.
+    // The store is checkpointed every `checkpoint_every` mutations:
+    // a restart replays, at most, that many commands on top of the last checkpoint.
+    pub fn supervised(self, checkpoint_every: usize) -> Self {
+        Self {
+            checkpoint_every: Some(checkpoint_every.max(1)),
+            ..self
+        }
+    }
+
+    // The order in which queued reads and writes are processed: FIFO by default.
+    // With any other policy, reads are queued apart from writes, with room for `capacity`
+    // commands of their own: they never wait behind writes the policy doesn't get to see.
+    pub fn scheduling(self, scheduling: SchedulingPolicy) -> Self {
+        Self { scheduling, ..self }
+    }
+
+    // How many reads may jump ahead of a waiting write before it's let through.
+    pub fn starvation_limit(self, starvation_limit: usize) -> Self {
+        Self {
+            starvation_limit,
+            ..self
+        }
+    }
+
+    // How many queued commands the scheduling policy gets to choose from.
+    // The server takes them out of its queues, which then have room for that many more:
+    // with any policy but FIFO, up to `2 * capacity + lookahead - 1` commands can be waiting.
+    pub fn lookahead(self, lookahead: usize) -> Self {
+        Self { lookahead, ..self }
+    }
+
+    pub fn launch(mut self) -> ServerHandle {
+        let persistence = self.persistence.take();
+        self.spawn(persistence, 0)
+    }
+
+    // Launches `workers` server threads, each owning a disjoint range of ticket ids.
+    // Every worker gets its own queue, with room for `capacity` commands.
+    pub fn launch_partitioned(mut self, workers: usize) -> PartitionedHandle {
+        assert!(workers > 0, "A server needs at least one worker");
+        let partitions = Partitions::new(workers);
+        // Each worker flushes its own tickets in here: the hook is invoked
+        // once, with all of them, after every worker has stopped.
+        let flushed: Arc<Mutex<Vec<Ticket>>> = Arc::default();
+        let persistence = self.persistence.take();
+        let handles = (0..workers)
+            .map(|partition| {
+                let flushed = flushed.clone();
+                let collect = move |tickets: &[Ticket]| {
+                    flushed.lock().unwrap().extend_from_slice(tickets);
+                    Ok(())
+                };
+                let collect: Option<Box<dyn Persistence>> = Some(Box::new(collect));
+                self.spawn(collect, partitions.first_id(partition))
+            })
+            .collect();
+        PartitionedHandle::new(handles, partitions, persistence, flushed)
+    }
+
+    fn spawn(&self, persistence: Option<Box<dyn Persistence>>, first_id: u64) -> ServerHandle {
+        // In FIFO order, reads must not get ahead of writes: they share the same lane.
+        let lane_count = match self.scheduling {
+            SchedulingPolicy::Fifo => 1,
+            _ => 2,
+        };
+        let (mut handles, mailbox) = actor::mailbox_with_lanes(self.capacity, lane_count);
+        let writes = handles.pop().unwrap();
+        let reads = handles.pop().unwrap_or_else(|| writes.clone());
+        let metrics = Arc::new(Metrics::new(self.capacity * lane_count));
+        let lanes = Lanes::new(self.scheduling, self.starvation_limit, self.lookahead);
+        let server = Server::new(
+            self.clock.clone(),
+            self.checkpoint_every,
+            (self.storage)(first_id),
+            metrics.clone(),
+        );
+        let status = Arc::new(Mutex::new(SupervisorStatus::default()));
+        // The server reports to the same subscriber as whoever launched it.
+        let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+        let thread = {
+            let status = status.clone();
+            std::thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    server::run(mailbox, lanes, server, persistence, status)
+                })
+            })
+        };
+        let client = TicketStoreClient {
+            reads,
+            writes,
+            timeout: TicketStoreClient::DEFAULT_TIMEOUT,
+            backpressure: Backpressure::default(),
+            counters: Arc::default(),
+            accepting: Arc::new(AtomicBool::new(true)),
+            metrics,
+        };
+        ServerHandle::new(client, thread, status)
+    }
+}
+
+enum Command {
+    Insert {
+        draft: TicketDraft,
+        response_channel: Responder<TicketId>,
+    },
+    Get {
+        id: TicketId,
+        response_channel: Responder<Option<TicketHandle>>,
+    },
+    Update {
+        patch: TicketPatch,
+        response_channel: Responder<Result<(), EditError>>,
+    },
+    Checkout {
+        id: TicketId,
+        holder: String,
+        duration: Duration,
+        response_channel: Responder<Result<Lease, EditError>>,
+    },
+    Renew {
+        lease: Lease,
+        duration: Duration,
+        response_channel: Responder<Result<Lease, EditError>>,
+    },
+    Commit {
+        lease: Lease,
+        patch: TicketPatch,
+        response_channel: Responder<Result<(), EditError>>,
+    },
+    Abandon {
+        lease: Lease,
+        response_channel: Responder<Result<(), EditError>>,
+    },
+    InsertMany {
+        drafts: Vec<TicketDraft>,
+        response_channel: Responder<Vec<TicketId>>,
+    },
+    GetMany {
+        ids: Vec<TicketId>,
+        response_channel: Responder<Vec<Option<TicketHandle>>>,
+    },
+    PatchMany {
+        patches: Vec<TicketPatch>,
+        response_channel: Responder<Vec<Result<(), EditError>>>,
+    },
+    PeekNext {
+        response_channel: Responder<Option<TicketId>>,
+    },
+    ClaimNext {
+        assignee: String,
+        response_channel: Responder<Option<TicketId>>,
+    },
+    Delete {
+        id: TicketId,
+        response_channel: Responder<Result<Option<TicketHandle>, EditError>>,
+    },
+    List {
+        response_channel: Responder<Vec<Ticket>>,
+    },
+    Subscribe {
+        response_channel: Responder<Receiver<TicketEvent>>,
+    },
+    // Sent by `ServerHandle::shutdown`, once clients have stopped sending new commands.
+    Shutdown,
+}
+
+// Where the server sends its response, along with what it needs to trace the request.
+struct Responder<T> {
+    reply: Reply<T>,
+    context: RequestContext,
+}
+
+impl<T> Responder<T> {
+    fn send(self, value: T) {
+        self.reply.send(value);
+    }
+}
+
+impl Command {
+    fn kind(&self) -> Option<CommandKind> {
+        let kind = match self {
+            Command::Insert { .. } => CommandKind::Insert,
+            Command::InsertMany { .. } => CommandKind::InsertMany,
+            Command::Get { .. } => CommandKind::Get,
+            Command::GetMany { .. } => CommandKind::GetMany,
+            Command::Update { .. } => CommandKind::Update,
+            Command::Checkout { .. } => CommandKind::Checkout,
+            Command::Renew { .. } => CommandKind::Renew,
+            Command::Commit { .. } => CommandKind::Commit,
+            Command::Abandon { .. } => CommandKind::Abandon,
+            Command::PatchMany { .. } => CommandKind::PatchMany,
+            Command::PeekNext { .. } => CommandKind::PeekNext,
+            Command::ClaimNext { .. } => CommandKind::ClaimNext,
+            Command::Delete { .. } => CommandKind::Delete,
+            Command::List { .. } => CommandKind::List,
+            Command::Subscribe { .. } => CommandKind::Subscribe,
+            Command::Shutdown => return None,
+        };
+        Some(kind)
+    }
+
+    fn context(&self) -> Option<&RequestContext> {
+        let context = match self {
+            Command::Insert {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::InsertMany {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Get {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::GetMany {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Update {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Checkout {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Renew {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Commit {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Abandon {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::PatchMany {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::PeekNext {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::ClaimNext {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Delete {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::List {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Subscribe {
+                response_channel, ..
+            } => &response_channel.context,
+            Command::Shutdown => return None,
+        };
+        Some(context)
+    }
+}
+
+fn handle(store: &mut TicketStore, command: Command) {
+    match command {
+        Command::Insert {
+            draft,
+            response_channel,
+        } => {
+            let id = store.add_ticket(draft);
+            response_channel.send(id);
+        }
+        Command::Get {
+            id,
+            response_channel,
+        } => {
+            let ticket = store.get(id);
+            response_channel.send(ticket);
+        }
+        Command::Update {
+            patch,
+            response_channel,
+        } => {
+            response_channel.send(store.update(patch));
+        }
+        Command::Checkout {
+            id,
+            holder,
+            duration,
+            response_channel,
+        } => {
+            response_channel.send(store.checkout(id, holder, duration));
+        }
+        Command::Renew {
+            lease,
+            duration,
+            response_channel,
+        } => {
+            response_channel.send(store.renew(&lease, duration));
+        }
+        Command::Commit {
+            lease,
+            patch,
+            response_channel,
+        } => {
+            response_channel.send(store.commit(&lease, patch));
+        }
+        Command::Abandon {
+            lease,
+            response_channel,
+        } => {
+            response_channel.send(store.abandon(&lease));
+        }
+        Command::InsertMany {
+            drafts,
+            response_channel,
+        } => {
+            let ids = drafts
+                .into_iter()
+                .map(|draft| store.add_ticket(draft))
+                .collect();
+            response_channel.send(ids);
+        }
+        Command::GetMany {
+            ids,
+            response_channel,
+        } => {
+            let tickets = ids.into_iter().map(|id| store.get(id)).collect();
+            response_channel.send(tickets);
+        }
+        Command::PatchMany {
+            patches,
+            response_channel,
+        } => {
+            let results = patches
+                .into_iter()
+                .map(|patch| store.update(patch))
+                .collect();
+            response_channel.send(results);
+        }
+        Command::PeekNext { response_channel } => {
+            response_channel.send(store.peek_next());
+        }
+        Command::ClaimNext {
+            assignee,
+            response_channel,
+        } => {
+            response_channel.send(store.claim_next(assignee));
+        }
+        Command::Delete {
+            id,
+            response_channel,
+        } => {
+            response_channel.send(store.delete(id));
+        }
+        Command::List { response_channel } => {
+            response_channel.send(store.snapshot());
+        }
+        Command::Subscribe { response_channel } => {
+            response_channel.send(store.subscribe());
+        }
+        Command::Shutdown => {}
+    }
+}
@@ -0,0 +1,142 @@
+// Locking several tickets at once, without deadlocking.
+//
+// If a thread locks ticket 1 then ticket 2 while another locks ticket 2 then ticket 1,
+// each may end up waiting for the other forever. We avoid that by always acquiring
+// ticket locks in ascending `TicketId` order: `LockSet` does it for you.
+//
+// With order checking on (the default in debug builds), every lock taken through this module
+// is tracked per thread: acquiring a ticket while holding one with a greater (or the same) id
+// panics, naming both tickets, even if that particular run didn't deadlock.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::data::Ticket;
+use crate::store::{TicketHandle, TicketId};
+use crate::sync::MutexGuard;
+
+static CHECK_ORDER: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+thread_local! {
+    // Tickets locked by the current thread, in acquisition order.
+    static HELD: RefCell<Vec<TicketId>> = const { RefCell::new(Vec::new()) };
+}
+
+// Turns lock-order checking on or off, for every thread.
+pub fn check_lock_order(enabled: bool) {
+    CHECK_ORDER.store(enabled, Ordering::Relaxed);
+}
+
+// A set of tickets that are always locked together, in id order.
+#[derive(Clone, Default)]
+pub struct LockSet {
+    tickets: BTreeMap<TicketId, TicketHandle>,
+}
+
+impl LockSet {
+    // Duplicates are ignored: each ticket is locked once.
+    pub fn new<I>(tickets: I) -> Self
+    where
+        I: IntoIterator<Item = (TicketId, TicketHandle)>,
+    {
+        Self {
+            tickets: tickets.into_iter().collect(),
+        }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = TicketId> + '_ {
+        self.tickets.keys().copied()
+    }
+
+    // Blocks until every ticket in the set is locked.
+    pub fn lock(&self) -> Guards<'_> {
+        Guards {
+            guards: self
+                .tickets
+                .iter()
+                .map(|(id, ticket)| (*id, lock_ticket(ticket)))
+                .collect(),
+        }
+    }
+}
+
+// The locks on every ticket of a `LockSet`. They're released when this is dropped.
+pub struct Guards<'a> {
+    guards: BTreeMap<TicketId, TicketGuard<'a>>,
+}
+
+impl Guards<'_> {
+    pub fn get(&self, id: TicketId) -> Option<&Ticket> {
+        self.guards.get(&id).map(|guard| &**guard)
+    }
+
+    pub fn get_mut(&mut self, id: TicketId) -> Option<&mut Ticket> {
+        self.guards.get_mut(&id).map(|guard| &mut **guard)
+    }
+
+    // Two distinct tickets, both mutably, e.g. to swap some of their fields.
+    pub fn pair_mut(&mut self, a: TicketId, b: TicketId) -> Option<(&mut Ticket, &mut Ticket)> {
+        if a == b {
+            return None;
+        }
+        let mut a_guard = None;
+        let mut b_guard = None;
+        for (id, guard) in self.guards.iter_mut() {
+            if *id == a {
+                a_guard = Some(&mut **guard);
+            } else if *id == b {
+                b_guard = Some(&mut **guard);
+            }
+        }
+        a_guard.zip(b_guard)
+    }
+}
+
+// The lock on a single ticket, tracked for lock-order checking.
+pub struct TicketGuard<'a> {
+    id: TicketId,
+    guard: MutexGuard<'a, Ticket>,
+}
+
+// Locks one ticket, taking part in lock-order checking.
+// Use it instead of `TicketHandle::lock` when holding other tickets' locks.
+pub fn lock_ticket(ticket: &TicketHandle) -> TicketGuard<'_> {
+    let id = ticket.id();
+    if CHECK_ORDER.load(Ordering::Relaxed) {
+        HELD.with_borrow(|held| {
+            if let Some(held) = held.iter().find(|held| **held >= id) {
+                panic!(
+                    "Lock-order inversion: acquiring ticket {id:?} while holding ticket {held:?}"
+                );
+            }
+        });
+    }
+    let guard = ticket.lock().unwrap();
+    HELD.with_borrow_mut(|held| held.push(id));
+    TicketGuard { id, guard }
+}
+
+impl Drop for TicketGuard<'_> {
+    fn drop(&mut self) {
+        HELD.with_borrow_mut(|held| {
+            if let Some(position) = held.iter().rposition(|held| *held == self.id) {
+                held.remove(position);
+            }
+        });
+    }
+}
+
+impl Deref for TicketGuard<'_> {
+    type Target = Ticket;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl DerefMut for TicketGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
@@ -0,0 +1,356 @@
+// What the server has been up to: how many commands of each kind it processed,
+// how long they took, how full its queue is and how often clients found it full.
+//
+// The server and its clients share the same `Metrics`: read them with `TicketStoreClient::metrics`.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::backpressure::{OverloadCount, OverloadStats};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommandKind {
+    Insert,
+    InsertMany,
+    Get,
+    GetMany,
+    Update,
+    PatchMany,
+    Checkout,
+    Renew,
+    Commit,
+    Abandon,
+    PeekNext,
+    ClaimNext,
+    Delete,
+    List,
+    Subscribe,
+}
+
+impl CommandKind {
+    pub const ALL: [CommandKind; 15] = [
+        CommandKind::Insert,
+        CommandKind::InsertMany,
+        CommandKind::Get,
+        CommandKind::GetMany,
+        CommandKind::Update,
+        CommandKind::PatchMany,
+        CommandKind::Checkout,
+        CommandKind::Renew,
+        CommandKind::Commit,
+        CommandKind::Abandon,
+        CommandKind::PeekNext,
+        CommandKind::ClaimNext,
+        CommandKind::Delete,
+        CommandKind::List,
+        CommandKind::Subscribe,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CommandKind::Insert => "insert",
+            CommandKind::InsertMany => "insert_many",
+            CommandKind::Get => "get",
+            CommandKind::GetMany => "get_many",
+            CommandKind::Update => "update",
+            CommandKind::PatchMany => "patch_many",
+            CommandKind::Checkout => "checkout",
+            CommandKind::Renew => "renew",
+            CommandKind::Commit => "commit",
+            CommandKind::Abandon => "abandon",
+            CommandKind::PeekNext => "peek_next",
+            CommandKind::ClaimNext => "claim_next",
+            CommandKind::Delete => "delete",
+            CommandKind::List => "list",
+            CommandKind::Subscribe => "subscribe",
+        }
+    }
+}
+
+// How a request failed, as seen by the client that sent it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorKind {
+    Overloaded,
+    Disconnected,
+    ShuttingDown,
+    RequestDropped,
+    TimedOut,
+    Edit,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 6] = [
+        ErrorKind::Overloaded,
+        ErrorKind::Disconnected,
+        ErrorKind::ShuttingDown,
+        ErrorKind::RequestDropped,
+        ErrorKind::TimedOut,
+        ErrorKind::Edit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Overloaded => "overloaded",
+            ErrorKind::Disconnected => "disconnected",
+            ErrorKind::ShuttingDown => "shutting_down",
+            ErrorKind::RequestDropped => "request_dropped",
+            ErrorKind::TimedOut => "timed_out",
+            ErrorKind::Edit => "edit",
+        }
+    }
+}
+
+// Upper bounds of the histogram buckets. Anything slower ends up in an extra, unbounded bucket.
+pub const BUCKETS: [Duration; 10] = [
+    Duration::from_micros(10),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    capacity: usize,
+    commands: [AtomicU64; CommandKind::ALL.len()],
+    // Commands sent by clients that the server hasn't picked up yet.
+    // Clients count a command once it's in the queue, so the server may get to it first:
+    // the count can briefly go negative.
+    queued: AtomicI64,
+    processing: Histogram,
+    latency: Histogram,
+    errors: [AtomicU64; ErrorKind::ALL.len()],
+}
+
+impl Metrics {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            commands: Default::default(),
+            queued: AtomicI64::new(0),
+            processing: Histogram::default(),
+            latency: Histogram::default(),
+            errors: Default::default(),
+        }
+    }
+
+    pub(crate) fn enqueued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn processed(&self, kind: CommandKind, took: Duration) {
+        self.commands[kind as usize].fetch_add(1, Ordering::Relaxed);
+        self.processing.record(took);
+    }
+
+    // The client is done with a request, whether it got a response or not.
+    pub(crate) fn responded(&self, took: Duration, error: Option<ErrorKind>) {
+        self.latency.record(took);
+        if let Some(error) = error {
+            self.errors[error as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self, overloads: OverloadStats) -> MetricsSnapshot {
+        MetricsSnapshot {
+            commands: CommandKind::ALL
+                .into_iter()
+                .map(|kind| (kind, self.commands[kind as usize].load(Ordering::Relaxed)))
+                .collect(),
+            processing: self.processing.snapshot(),
+            latency: self.latency.snapshot(),
+            errors: ErrorKind::ALL
+                .into_iter()
+                .map(|kind| (kind, self.errors[kind as usize].load(Ordering::Relaxed)))
+                .collect(),
+            queue_depth: self.queued.load(Ordering::Relaxed).max(0) as usize,
+            capacity: self.capacity,
+            overloads,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    // One more than `BUCKETS`, for the unbounded bucket.
+    counts: [AtomicU64; BUCKETS.len() + 1],
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn record(&self, value: Duration) {
+        let bucket = BUCKETS.partition_point(|bound| *bound < value);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(value.as_nanos()).unwrap_or(u64::MAX);
+        self.sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            counts: self
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    // How many values fell in each bucket: `counts[i]` is the number of values
+    // in `(BUCKETS[i - 1], BUCKETS[i]]`, and the last entry counts the ones above every bound.
+    pub counts: Vec<u64>,
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    // How many commands of each kind the server processed.
+    pub commands: BTreeMap<CommandKind, u64>,
+    // Time spent by the server processing each command.
+    pub processing: HistogramSnapshot,
+    // Time between a client sending a command and getting the response back,
+    // or giving up on it.
+    pub latency: HistogramSnapshot,
+    // How many requests failed, by kind of error.
+    pub errors: BTreeMap<ErrorKind, u64>,
+    // Commands waiting to be picked up by the server, out of `capacity`.
+    pub queue_depth: usize,
+    pub capacity: usize,
+    pub overloads: OverloadStats,
+}
+
+// Renders the snapshot in the Prometheus text exposition format.
+pub fn render_prometheus(snapshot: &MetricsSnapshot) -> String {
+    let mut out = String::new();
+    header(
+        &mut out,
+        "ticket_store_commands_total",
+        "counter",
+        "Commands processed by the server.",
+    );
+    for (kind, count) in &snapshot.commands {
+        let _ = writeln!(
+            out,
+            "ticket_store_commands_total{{command=\"{}\"}} {count}",
+            kind.name()
+        );
+    }
+    render_histogram(
+        &mut out,
+        "ticket_store_processing_seconds",
+        "Time spent by the server processing a command.",
+        &snapshot.processing,
+    );
+    render_histogram(
+        &mut out,
+        "ticket_store_request_latency_seconds",
+        "Time between a client sending a command and receiving the response, or giving up.",
+        &snapshot.latency,
+    );
+    header(
+        &mut out,
+        "ticket_store_request_errors_total",
+        "counter",
+        "Requests that failed, by kind of error.",
+    );
+    for (kind, count) in &snapshot.errors {
+        let _ = writeln!(
+            out,
+            "ticket_store_request_errors_total{{error=\"{}\"}} {count}",
+            kind.name()
+        );
+    }
+    header(
+        &mut out,
+        "ticket_store_queue_depth",
+        "gauge",
+        "Commands waiting to be processed.",
+    );
+    let _ = writeln!(out, "ticket_store_queue_depth {}", snapshot.queue_depth);
+    header(
+        &mut out,
+        "ticket_store_queue_capacity",
+        "gauge",
+        "How many commands can wait to be processed.",
+    );
+    let _ = writeln!(out, "ticket_store_queue_capacity {}", snapshot.capacity);
+
+    let OverloadStats {
+        fail_fast,
+        block,
+        block_for,
+        retry,
+    } = snapshot.overloads;
+    let strategies = [
+        ("fail_fast", fail_fast),
+        ("block", block),
+        ("block_for", block_for),
+        ("retry", retry),
+    ];
+    header(
+        &mut out,
+        "ticket_store_overloaded_total",
+        "counter",
+        "Requests that found the queue full on their first attempt.",
+    );
+    for (strategy, OverloadCount { overloaded, .. }) in strategies {
+        let _ = writeln!(
+            out,
+            "ticket_store_overloaded_total{{strategy=\"{strategy}\"}} {overloaded}"
+        );
+    }
+    header(
+        &mut out,
+        "ticket_store_rejected_total",
+        "counter",
+        "Requests that failed because the queue was full.",
+    );
+    for (strategy, OverloadCount { rejected, .. }) in strategies {
+        let _ = writeln!(
+            out,
+            "ticket_store_rejected_total{{strategy=\"{strategy}\"}} {rejected}"
+        );
+    }
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn render_histogram(out: &mut String, name: &str, help: &str, histogram: &HistogramSnapshot) {
+    header(out, name, "histogram", help);
+    // Prometheus buckets are cumulative.
+    let mut cumulative = 0;
+    for (bound, count) in BUCKETS.iter().zip(&histogram.counts) {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{le=\"{}\"}} {cumulative}",
+            bound.as_secs_f64()
+        );
+    }
+    let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", histogram.count());
+    let _ = writeln!(out, "{name}_sum {}", histogram.sum.as_secs_f64());
+    let _ = writeln!(out, "{name}_count {}", histogram.count());
+}
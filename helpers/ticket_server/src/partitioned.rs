@@ -0,0 +1,381 @@
+// A ticket server made of several worker threads, rather than one.
+//
+// The id space is split into contiguous ranges, one per worker: each worker owns
+// the tickets whose id falls in its range, so commands about a ticket are routed
+// straight to the worker that owns it. Workers never share state, so they never
+// contend with each other. New tickets are handed to workers in a round-robin fashion.
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use actor::Pending;
+use tracing::Span;
+
+use crate::backpressure::Backpressure;
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::events::TicketEvent;
+use crate::leases::{EditError, Lease};
+use crate::metrics::MetricsSnapshot;
+use crate::queue;
+use crate::shutdown::{Persistence, ServerHandle, ShutdownReport};
+use crate::store::{TicketHandle, TicketId};
+use crate::trace;
+use crate::{ClientError, Command, Responder, TicketStoreClient};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Partitions {
+    workers: usize,
+    // How many ids each worker owns.
+    // A worker would need to allocate that many ids before running into the next range.
+    span: u64,
+}
+
+impl Partitions {
+    pub(crate) fn new(workers: usize) -> Self {
+        Self {
+            workers,
+            span: u64::MAX / workers as u64,
+        }
+    }
+
+    pub(crate) fn first_id(&self, partition: usize) -> u64 {
+        partition as u64 * self.span
+    }
+
+    fn of(&self, id: TicketId) -> usize {
+        ((id.0 / self.span) as usize).min(self.workers - 1)
+    }
+}
+
+#[derive(Clone)]
+pub struct PartitionedClient {
+    workers: Vec<TicketStoreClient>,
+    partitions: Partitions,
+    // Which worker gets the next insert. Shared by all the clones of a client.
+    next: Arc<AtomicUsize>,
+}
+
+impl PartitionedClient {
+    // Applies to the connection with every worker.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.map(|worker| worker.with_timeout(timeout))
+    }
+
+    // Applies to the connection with every worker.
+    pub fn with_backpressure(self, backpressure: Backpressure) -> Self {
+        self.map(|worker| worker.with_backpressure(backpressure))
+    }
+
+    // The metrics of each worker, in worker order.
+    pub fn metrics(&self) -> Vec<MetricsSnapshot> {
+        self.workers
+            .iter()
+            .map(TicketStoreClient::metrics)
+            .collect()
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, ClientError> {
+        let worker = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        self.workers[worker].insert(draft)
+    }
+
+    // The whole batch goes to a single worker.
+    pub fn insert_many(&self, drafts: Vec<TicketDraft>) -> Result<Vec<TicketId>, ClientError> {
+        let worker = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        self.workers[worker].insert_many(drafts)
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<TicketHandle>, ClientError> {
+        self.owner(id).get(id)
+    }
+
+    pub fn update(&self, patch: TicketPatch) -> Result<(), ClientError> {
+        self.owner(patch.id).update(patch)
+    }
+
+    pub fn get_many(&self, ids: Vec<TicketId>) -> Result<Vec<Option<TicketHandle>>, ClientError> {
+        self.scatter(
+            ids,
+            |id| *id,
+            |worker, ids| {
+                worker.submit(|response_channel| Command::GetMany {
+                    ids,
+                    response_channel,
+                })
+            },
+        )
+    }
+
+    pub fn patch_many(
+        &self,
+        patches: Vec<TicketPatch>,
+    ) -> Result<Vec<Result<(), EditError>>, ClientError> {
+        self.scatter(
+            patches,
+            |patch| patch.id,
+            |worker, patches| {
+                worker.submit(|response_channel| Command::PatchMany {
+                    patches,
+                    response_channel,
+                })
+            },
+        )
+    }
+
+    pub fn checkout(
+        &self,
+        id: TicketId,
+        holder: String,
+        duration: Duration,
+    ) -> Result<Lease, ClientError> {
+        self.owner(id).checkout(id, holder, duration)
+    }
+
+    pub fn renew(&self, lease: &Lease, duration: Duration) -> Result<Lease, ClientError> {
+        self.owner(lease.id).renew(lease, duration)
+    }
+
+    pub fn commit(&self, lease: Lease, patch: TicketPatch) -> Result<(), ClientError> {
+        self.owner(lease.id).commit(lease, patch)
+    }
+
+    pub fn abandon(&self, lease: Lease) -> Result<(), ClientError> {
+        self.owner(lease.id).abandon(lease)
+    }
+
+    pub fn delete(&self, id: TicketId) -> Result<Option<TicketHandle>, ClientError> {
+        self.owner(id).delete(id)
+    }
+
+    // The next ticket someone should pick up, across all workers.
+    // Each worker puts forward the head of its own queue: the most urgent one wins.
+    pub fn peek_next(&self) -> Result<Option<TicketId>, ClientError> {
+        Ok(self.next_in_line()?.map(|ticket| ticket.id))
+    }
+
+    // Assigns the most urgent ticket, across all workers, to `assignee`.
+    // Unlike with a single worker, this isn't atomic: if another client claims the ticket
+    // first, its worker hands out the next ticket in its own queue instead.
+    pub fn claim_next(&self, assignee: String) -> Result<Option<TicketId>, ClientError> {
+        loop {
+            let Some(ticket) = self.next_in_line()? else {
+                return Ok(None);
+            };
+            if let Some(id) = self.owner(ticket.id).claim_next(assignee.clone())? {
+                return Ok(Some(id));
+            }
+            // Its queue was emptied in the meantime: look at the others again.
+        }
+    }
+
+    // Events from every worker, merged into a single stream as they come.
+    // Events about the same ticket arrive in order, but there's no order across workers.
+    // The stream ends once every worker has shut down.
+    pub fn subscribe(&self) -> Result<Receiver<TicketEvent>, ClientError> {
+        let streams = self.broadcast(|response_channel| Command::Subscribe { response_channel })?;
+        let (sender, merged) = channel();
+        for stream in streams {
+            let sender = sender.clone();
+            // Stops at the first event nobody is listening to anymore.
+            thread::spawn(move || stream.into_iter().try_for_each(|event| sender.send(event)));
+        }
+        Ok(merged)
+    }
+
+    // A copy of every ticket, ordered by id.
+    // Workers are queried concurrently: each of them contributes a snapshot of its own tickets,
+    // but they are not taken at the same instant.
+    pub fn list(&self) -> Result<Vec<Ticket>, ClientError> {
+        // Ranges are laid out in worker order, so concatenating keeps tickets sorted by id.
+        Ok(self
+            .broadcast(|response_channel| Command::List { response_channel })?
+            .concat())
+    }
+
+    fn next_in_line(&self) -> Result<Option<Ticket>, ClientError> {
+        let heads = self.broadcast(|response_channel| Command::PeekNext { response_channel })?;
+        let mut candidates = Vec::new();
+        for id in heads.into_iter().flatten() {
+            // Deleted in the meantime: its worker will put forward another one next time.
+            if let Some(ticket) = self.owner(id).get(id)? {
+                candidates.push(ticket.current());
+            }
+        }
+        Ok(candidates.into_iter().max_by(queue::pick_order))
+    }
+
+    // Sends the same command to every worker, then collects their responses, in worker order.
+    fn broadcast<T, F>(&self, command: F) -> Result<Vec<T>, ClientError>
+    where
+        F: Fn(Responder<T>) -> Command,
+    {
+        let responses = self
+            .workers
+            .iter()
+            .map(|worker| Sent::new(worker, || worker.submit(&command)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.workers
+            .iter()
+            .zip(responses)
+            .map(|(worker, response)| response.wait(worker))
+            .collect()
+    }
+
+    // Splits a batch into one sub-batch per worker, sends them all,
+    // then puts the results back in the order of the original batch.
+    fn scatter<I, R, K, S>(&self, items: Vec<I>, key: K, send: S) -> Result<Vec<R>, ClientError>
+    where
+        K: Fn(&I) -> TicketId,
+        S: Fn(&TicketStoreClient, Vec<I>) -> Result<Pending<Vec<R>>, ClientError>,
+    {
+        let mut batches: Vec<Vec<I>> = self.workers.iter().map(|_| Vec::new()).collect();
+        let mut positions: Vec<Vec<usize>> = self.workers.iter().map(|_| Vec::new()).collect();
+        for (position, item) in items.into_iter().enumerate() {
+            let worker = self.partitions.of(key(&item));
+            batches[worker].push(item);
+            positions[worker].push(position);
+        }
+        let responses = self
+            .workers
+            .iter()
+            .zip(batches)
+            .map(|(worker, batch)| match batch.is_empty() {
+                true => Ok(None),
+                false => Sent::new(worker, || send(worker, batch)).map(Some),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut results: Vec<Option<R>> = positions.iter().flatten().map(|_| None).collect();
+        for ((worker, response), positions) in self.workers.iter().zip(responses).zip(positions) {
+            let Some(response) = response else {
+                continue;
+            };
+            let response = response.wait(worker)?;
+            for (position, result) in positions.into_iter().zip(response) {
+                results[position] = Some(result);
+            }
+        }
+        Ok(results.into_iter().map(|result| result.unwrap()).collect())
+    }
+
+    fn owner(&self, id: TicketId) -> &TicketStoreClient {
+        &self.workers[self.partitions.of(id)]
+    }
+
+    fn map<F>(self, f: F) -> Self
+    where
+        F: Fn(TicketStoreClient) -> TicketStoreClient,
+    {
+        Self {
+            workers: self.workers.into_iter().map(f).collect(),
+            ..self
+        }
+    }
+}
+
+// A request of a fan-out. Each gets a client span of its own, and so its own correlation id:
+// the span is entered again while waiting for the response.
+// It's timed by the worker it was sent to, like any other request.
+struct Sent<T> {
+    span: Span,
+    start: Instant,
+    pending: Pending<T>,
+}
+
+impl<T> Sent<T> {
+    fn new<S>(worker: &TicketStoreClient, submit: S) -> Result<Self, ClientError>
+    where
+        S: FnOnce() -> Result<Pending<T>, ClientError>,
+    {
+        let span = trace::client_span();
+        let start = Instant::now();
+        let pending = span
+            .in_scope(submit)
+            .inspect_err(|error| worker.record(start, Some(error)))?;
+        Ok(Self {
+            span,
+            start,
+            pending,
+        })
+    }
+
+    fn wait(self, worker: &TicketStoreClient) -> Result<T, ClientError> {
+        let response = self.span.in_scope(|| worker.wait(self.pending));
+        worker.record(self.start, response.as_ref().err());
+        response
+    }
+}
+
+// Returned by `ServerBuilder::launch_partitioned`: it owns every worker thread.
+// It dereferences to a `PartitionedClient`, so it can be used to talk to the server directly.
+pub struct PartitionedHandle {
+    client: PartitionedClient,
+    workers: Vec<ServerHandle>,
+    persistence: Option<Box<dyn Persistence>>,
+    flushed: Arc<Mutex<Vec<Ticket>>>,
+}
+
+impl PartitionedHandle {
+    pub(crate) fn new(
+        workers: Vec<ServerHandle>,
+        partitions: Partitions,
+        persistence: Option<Box<dyn Persistence>>,
+        flushed: Arc<Mutex<Vec<Ticket>>>,
+    ) -> Self {
+        let client = PartitionedClient {
+            workers: workers.iter().map(ServerHandle::client).collect(),
+            partitions,
+            next: Arc::default(),
+        };
+        Self {
+            client,
+            workers,
+            persistence,
+            flushed,
+        }
+    }
+
+    pub fn client(&self) -> PartitionedClient {
+        self.client.clone()
+    }
+
+    // Stops every worker, as `ServerHandle::shutdown` does, then flushes
+    // all the tickets through the persistence hook in one go.
+    // Returns an error if any of the worker threads panicked.
+    pub fn shutdown(self) -> std::thread::Result<ShutdownReport> {
+        // Stop accepting commands everywhere at once, before waiting for any worker to drain.
+        for worker in &self.workers {
+            worker.accepting.store(false, Ordering::Release);
+        }
+        let mut commands_processed = 0;
+        let mut panicked = None;
+        for worker in self.workers {
+            match worker.shutdown() {
+                Ok(report) => commands_processed += report.commands_processed,
+                Err(panic) => panicked = Some(panic),
+            }
+        }
+        if let Some(panic) = panicked {
+            return Err(panic);
+        }
+        let flushed = self.persistence.map(|mut persistence| {
+            let mut tickets = std::mem::take(&mut *self.flushed.lock().unwrap());
+            tickets.sort_by_key(|ticket| ticket.id);
+            persistence.flush(&tickets)
+        });
+        Ok(ShutdownReport {
+            commands_processed,
+            flushed,
+        })
+    }
+}
+
+impl Deref for PartitionedHandle {
+    type Target = PartitionedClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
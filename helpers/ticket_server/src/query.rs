@@ -0,0 +1,116 @@
+// Bulk queries over a set of tickets, spread across several threads.
+//
+// The tickets are split into contiguous chunks, one per worker, and every worker
+// runs the query on its own chunk inside a `thread::scope`: no need for `'static` data,
+// the workers borrow the slice directly. Partial results are merged in chunk order,
+// so the outcome doesn't depend on which worker finishes first.
+use std::collections::BTreeMap;
+use std::panic::resume_unwind;
+use std::thread;
+
+use crate::data::{Status, Ticket};
+use crate::store::TicketId;
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueryEngine {
+    threads: usize,
+}
+
+impl QueryEngine {
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "A query engine needs at least one thread");
+        Self { threads }
+    }
+
+    // The tickets matching `predicate`, in their original order.
+    pub fn filter<'a, P>(&self, tickets: &'a [Ticket], predicate: P) -> Vec<&'a Ticket>
+    where
+        P: Fn(&Ticket) -> bool + Sync,
+    {
+        self.run(tickets, |chunk| {
+            chunk.iter().filter(|t| predicate(t)).collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    pub fn count<P>(&self, tickets: &[Ticket], predicate: P) -> usize
+    where
+        P: Fn(&Ticket) -> bool + Sync,
+    {
+        self.run(tickets, |chunk| {
+            chunk.iter().filter(|t| predicate(t)).count()
+        })
+        .into_iter()
+        .sum()
+    }
+
+    // How many tickets there are for each key.
+    pub fn group_by<K, F>(&self, tickets: &[Ticket], key: F) -> BTreeMap<K, usize>
+    where
+        K: Ord + Send,
+        F: Fn(&Ticket) -> K + Sync,
+    {
+        let partials = self.run(tickets, |chunk| {
+            let mut counts = BTreeMap::new();
+            for ticket in chunk {
+                *counts.entry(key(ticket)).or_insert(0) += 1;
+            }
+            counts
+        });
+        let mut counts = BTreeMap::new();
+        for partial in partials {
+            for (key, count) in partial {
+                *counts.entry(key).or_insert(0) += count;
+            }
+        }
+        counts
+    }
+
+    pub fn count_by_status(&self, tickets: &[Ticket]) -> BTreeMap<Status, usize> {
+        self.group_by(tickets, |ticket| ticket.status)
+    }
+
+    // The tickets whose description contains `needle`, ignoring case, in their original order.
+    pub fn search_descriptions(&self, tickets: &[Ticket], needle: &str) -> Vec<TicketId> {
+        let needle = needle.to_lowercase();
+        self.filter(tickets, |ticket| {
+            ticket.description.as_str().to_lowercase().contains(&needle)
+        })
+        .into_iter()
+        .map(|ticket| ticket.id)
+        .collect()
+    }
+
+    // Runs `query` on each chunk, on its own thread.
+    // Results are returned in chunk order. If `query` panics, so do we, with the same payload.
+    fn run<'a, T, Q>(&self, tickets: &'a [Ticket], query: Q) -> Vec<T>
+    where
+        T: Send,
+        Q: Fn(&'a [Ticket]) -> T + Sync,
+    {
+        if tickets.is_empty() {
+            return Vec::new();
+        }
+        let chunk_size = tickets.len().div_ceil(self.threads);
+        let query = &query;
+        thread::scope(|scope| {
+            let workers: Vec<_> = tickets
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || query(chunk)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap_or_else(|panic| resume_unwind(panic)))
+                .collect()
+        })
+    }
+}
+
+impl Default for QueryEngine {
+    // One thread per core.
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
@@ -0,0 +1,128 @@
+// "What should I pick up next?"
+// Unassigned `ToDo` tickets, ordered by priority (highest first), then due date
+// (earliest first, tickets without one last), then age (oldest first).
+use crate::data::{Priority, Status, Ticket};
+use crate::store::TicketId;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::time::SystemTime;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    priority: Priority,
+    due_date: Option<SystemTime>,
+    created_at: SystemTime,
+    id: TicketId,
+}
+
+impl Entry {
+    fn new(ticket: &Ticket) -> Self {
+        Self {
+            priority: ticket.priority,
+            due_date: ticket.due_date,
+            created_at: ticket.created_at,
+            id: ticket.id,
+        }
+    }
+}
+
+impl Ord for Entry {
+    // `BinaryHeap` is a max-heap: the "greatest" entry is the one to pick up next.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_due_date = match (self.due_date, other.due_date) {
+            (Some(mine), Some(theirs)) => theirs.cmp(&mine),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        self.priority
+            .cmp(&other.priority)
+            .then(by_due_date)
+            .then_with(|| other.created_at.cmp(&self.created_at))
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// The queue is updated lazily: every time a ticket changes we push a fresh entry,
+// and outdated entries are dealt with when they reach the top of the heap.
+// A ticket can also change without the queue being told, e.g. through a handle:
+// its outdated entry is then replaced with one matching its current state.
+#[derive(Clone, Debug, Default)]
+pub struct WorkQueue {
+    heap: BinaryHeap<Entry>,
+    // The most recent entry pushed for each ticket that's ready to be picked up.
+    latest: BTreeMap<TicketId, Entry>,
+}
+
+impl WorkQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Must be called every time a ticket is created or modified.
+    pub fn track(&mut self, ticket: &Ticket) {
+        if is_ready(ticket) {
+            self.push(Entry::new(ticket));
+        } else {
+            self.latest.remove(&ticket.id);
+        }
+    }
+
+    // The next ticket to pick up, if any. Tickets `available` turns down are skipped:
+    // they keep their place in the queue for next time.
+    // `lookup` must return the current state of the given ticket.
+    pub fn peek_next<F, A>(&mut self, lookup: F, mut available: A) -> Option<TicketId>
+    where
+        F: Fn(TicketId) -> Option<Ticket>,
+        A: FnMut(TicketId) -> bool,
+    {
+        let mut skipped = Vec::new();
+        let next = loop {
+            let Some(entry) = self.heap.peek() else {
+                break None;
+            };
+            let current = lookup(entry.id)
+                .filter(is_ready)
+                .map(|ticket| Entry::new(&ticket));
+            if current.as_ref() == Some(entry) {
+                if available(entry.id) {
+                    break Some(entry.id);
+                }
+                skipped.push(self.heap.pop().unwrap());
+                continue;
+            }
+            let outdated = self.heap.pop().unwrap();
+            match current {
+                Some(current) => self.push(current),
+                None => {
+                    self.latest.remove(&outdated.id);
+                }
+            }
+        };
+        self.heap.extend(skipped);
+        next
+    }
+
+    // Does nothing if an identical entry is already queued.
+    fn push(&mut self, entry: Entry) {
+        if self.latest.get(&entry.id) != Some(&entry) {
+            self.latest.insert(entry.id, entry.clone());
+            self.heap.push(entry);
+        }
+    }
+}
+
+// `Greater` if `a` should be picked up before `b`, whether or not they're in the same queue.
+pub(crate) fn pick_order(a: &Ticket, b: &Ticket) -> Ordering {
+    Entry::new(a).cmp(&Entry::new(b))
+}
+
+fn is_ready(ticket: &Ticket) -> bool {
+    ticket.status == Status::ToDo && ticket.assignee.is_none()
+}
@@ -0,0 +1,395 @@
+// Talking to a ticket server that lives in another process, over a Unix domain socket.
+//
+// `serve` exposes a server to other processes: each connection gets its own thread,
+// which decodes requests, forwards them to the server through a regular `TicketStoreClient`
+// and sends the outcome back. `RemoteClient` mirrors the `TicketStoreClient` API, except that
+// tickets come back by value: a handle on a ticket can't cross a process boundary.
+use std::io;
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::leases::{EditError, Lease};
+use crate::store::{TicketHandle, TicketId};
+use crate::wire::{self, Decode, Encode};
+use crate::{ClientError, TicketStoreClient};
+
+enum Request {
+    Insert(TicketDraft),
+    InsertMany(Vec<TicketDraft>),
+    Get(TicketId),
+    GetMany(Vec<TicketId>),
+    Update(TicketPatch),
+    PatchMany(Vec<TicketPatch>),
+    Checkout(TicketId, String, Duration),
+    Renew(Lease, Duration),
+    Commit(Lease, TicketPatch),
+    Abandon(Lease),
+    PeekNext,
+    ClaimNext(String),
+    Delete(TicketId),
+    List,
+}
+
+impl Encode for Request {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Request::Insert(draft) => {
+                buffer.push(0);
+                draft.encode(buffer);
+            }
+            Request::InsertMany(drafts) => {
+                buffer.push(1);
+                drafts.encode(buffer);
+            }
+            Request::Get(id) => {
+                buffer.push(2);
+                id.encode(buffer);
+            }
+            Request::GetMany(ids) => {
+                buffer.push(3);
+                ids.encode(buffer);
+            }
+            Request::Update(patch) => {
+                buffer.push(4);
+                patch.encode(buffer);
+            }
+            Request::PatchMany(patches) => {
+                buffer.push(5);
+                patches.encode(buffer);
+            }
+            Request::Checkout(id, holder, duration) => {
+                buffer.push(6);
+                id.encode(buffer);
+                holder.encode(buffer);
+                duration.encode(buffer);
+            }
+            Request::Renew(lease, duration) => {
+                buffer.push(7);
+                lease.encode(buffer);
+                duration.encode(buffer);
+            }
+            Request::Commit(lease, patch) => {
+                buffer.push(8);
+                lease.encode(buffer);
+                patch.encode(buffer);
+            }
+            Request::Abandon(lease) => {
+                buffer.push(9);
+                lease.encode(buffer);
+            }
+            Request::PeekNext => buffer.push(10),
+            Request::ClaimNext(assignee) => {
+                buffer.push(11);
+                assignee.encode(buffer);
+            }
+            Request::Delete(id) => {
+                buffer.push(12);
+                id.encode(buffer);
+            }
+            Request::List => buffer.push(13),
+        }
+    }
+}
+
+impl Decode for Request {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(match wire::tag(input)? {
+            0 => Request::Insert(Decode::decode(input)?),
+            1 => Request::InsertMany(Decode::decode(input)?),
+            2 => Request::Get(Decode::decode(input)?),
+            3 => Request::GetMany(Decode::decode(input)?),
+            4 => Request::Update(Decode::decode(input)?),
+            5 => Request::PatchMany(Decode::decode(input)?),
+            6 => Request::Checkout(
+                Decode::decode(input)?,
+                Decode::decode(input)?,
+                Decode::decode(input)?,
+            ),
+            7 => Request::Renew(Decode::decode(input)?, Decode::decode(input)?),
+            8 => Request::Commit(Decode::decode(input)?, Decode::decode(input)?),
+            9 => Request::Abandon(Decode::decode(input)?),
+            10 => Request::PeekNext,
+            11 => Request::ClaimNext(Decode::decode(input)?),
+            12 => Request::Delete(Decode::decode(input)?),
+            13 => Request::List,
+            _ => return Err(wire::invalid("invalid request")),
+        })
+    }
+}
+
+// A peer that doesn't read its responses gets disconnected after this long.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+// How long to wait before accepting connections again after a failure,
+// e.g. because we ran out of file descriptors.
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+// Accepts connections on `path` until dropped. The socket file is removed on drop.
+// Dropping it also closes the connections it accepted, once their requests in flight
+// have been responded to.
+pub struct RemoteServer {
+    path: PathBuf,
+    stopping: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// Makes the server reachable by other processes, through a socket at `path`.
+pub fn serve<P: AsRef<Path>>(path: P, client: TicketStoreClient) -> io::Result<RemoteServer> {
+    let path = path.as_ref().to_path_buf();
+    let listener = UnixListener::bind(&path)?;
+    let stopping = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stopping = stopping.clone();
+        std::thread::spawn(move || {
+            let mut connections: Vec<Connection> = Vec::new();
+            let mut backoff = Duration::ZERO;
+            for stream in listener.incoming() {
+                if stopping.load(Ordering::Acquire) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) if is_transient(&e) => continue,
+                    // Retrying straight away would most likely fail the same way.
+                    Err(_) => {
+                        backoff = (backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+                        std::thread::sleep(backoff);
+                        continue;
+                    }
+                };
+                backoff = Duration::ZERO;
+                connections.retain(|connection| !connection.thread.is_finished());
+                // If we can't set it up, dropping the stream hangs up on the peer.
+                if let Ok(connection) = Connection::spawn(stream, client.clone()) {
+                    connections.push(connection);
+                }
+            }
+            for connection in connections {
+                connection.close();
+            }
+        })
+    };
+    Ok(RemoteServer {
+        path,
+        stopping,
+        thread: Some(thread),
+    })
+}
+
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Release);
+        // Wake up the listener, so that it notices it has to stop.
+        let _ = UnixStream::connect(&self.path);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Errors that only concern the connection we were about to accept.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+// A connection being served on its own thread.
+struct Connection {
+    // A clone of the stream the thread reads from, to interrupt it.
+    stream: UnixStream,
+    thread: JoinHandle<()>,
+}
+
+impl Connection {
+    fn spawn(stream: UnixStream, client: TicketStoreClient) -> io::Result<Self> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let clone = stream.try_clone()?;
+        let thread = std::thread::spawn(move || {
+            handle_connection(&stream, client);
+            // Our clone would keep the connection open otherwise.
+            let _ = stream.shutdown(Shutdown::Both);
+        });
+        Ok(Self {
+            stream: clone,
+            thread,
+        })
+    }
+
+    // The thread stops reading requests, but still writes the response it's working on.
+    fn close(self) {
+        let _ = self.stream.shutdown(Shutdown::Read);
+        let _ = self.thread.join();
+    }
+}
+
+// Serves requests until the peer hangs up or sends something we can't make sense of.
+fn handle_connection(mut connection: &UnixStream, client: TicketStoreClient) {
+    while let Ok(frame) = wire::read_frame(&mut connection) {
+        let Ok(request) = wire::from_bytes(&frame) else {
+            return;
+        };
+        let response = respond(&client, request);
+        if wire::write_frame(&mut connection, &response).is_err() {
+            return;
+        }
+    }
+}
+
+fn respond(client: &TicketStoreClient, request: Request) -> Vec<u8> {
+    // Tickets are sent by value.
+    let copy = |ticket: TicketHandle| ticket.lock().unwrap().clone();
+    match request {
+        Request::Insert(draft) => wire::to_bytes(&client.insert(draft)),
+        Request::InsertMany(drafts) => wire::to_bytes(&client.insert_many(drafts)),
+        Request::Get(id) => wire::to_bytes(&client.get(id).map(|ticket| ticket.map(copy))),
+        Request::GetMany(ids) => wire::to_bytes(
+            &client
+                .get_many(ids)
+                .map(|tickets| tickets.into_iter().map(|t| t.map(copy)).collect::<Vec<_>>()),
+        ),
+        Request::Update(patch) => wire::to_bytes(&client.update(patch)),
+        Request::PatchMany(patches) => wire::to_bytes(&client.patch_many(patches)),
+        Request::Checkout(id, holder, duration) => {
+            wire::to_bytes(&client.checkout(id, holder, duration))
+        }
+        Request::Renew(lease, duration) => wire::to_bytes(&client.renew(&lease, duration)),
+        Request::Commit(lease, patch) => wire::to_bytes(&client.commit(lease, patch)),
+        Request::Abandon(lease) => wire::to_bytes(&client.abandon(lease)),
+        Request::PeekNext => wire::to_bytes(&client.peek_next()),
+        Request::ClaimNext(assignee) => wire::to_bytes(&client.claim_next(assignee)),
+        Request::Delete(id) => wire::to_bytes(&client.delete(id).map(|ticket| ticket.map(copy))),
+        Request::List => wire::to_bytes(&client.list()),
+    }
+}
+
+// Clones share the same connection. If it breaks (or a request times out),
+// it's re-established on the next request.
+#[derive(Clone)]
+pub struct RemoteClient {
+    path: PathBuf,
+    timeout: Duration,
+    connection: Arc<Mutex<Option<UnixStream>>>,
+}
+
+impl RemoteClient {
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let connection = UnixStream::connect(&path)?;
+        Ok(Self {
+            path,
+            timeout: TicketStoreClient::DEFAULT_TIMEOUT,
+            connection: Arc::new(Mutex::new(Some(connection))),
+        })
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn insert(&self, draft: TicketDraft) -> Result<TicketId, ClientError> {
+        self.call(Request::Insert(draft))
+    }
+
+    pub fn insert_many(&self, drafts: Vec<TicketDraft>) -> Result<Vec<TicketId>, ClientError> {
+        self.call(Request::InsertMany(drafts))
+    }
+
+    pub fn get(&self, id: TicketId) -> Result<Option<Ticket>, ClientError> {
+        self.call(Request::Get(id))
+    }
+
+    pub fn get_many(&self, ids: Vec<TicketId>) -> Result<Vec<Option<Ticket>>, ClientError> {
+        self.call(Request::GetMany(ids))
+    }
+
+    pub fn update(&self, patch: TicketPatch) -> Result<(), ClientError> {
+        self.call(Request::Update(patch))
+    }
+
+    pub fn patch_many(
+        &self,
+        patches: Vec<TicketPatch>,
+    ) -> Result<Vec<Result<(), EditError>>, ClientError> {
+        self.call(Request::PatchMany(patches))
+    }
+
+    pub fn checkout(
+        &self,
+        id: TicketId,
+        holder: String,
+        duration: Duration,
+    ) -> Result<Lease, ClientError> {
+        self.call(Request::Checkout(id, holder, duration))
+    }
+
+    pub fn renew(&self, lease: &Lease, duration: Duration) -> Result<Lease, ClientError> {
+        self.call(Request::Renew(lease.clone(), duration))
+    }
+
+    pub fn commit(&self, lease: Lease, patch: TicketPatch) -> Result<(), ClientError> {
+        self.call(Request::Commit(lease, patch))
+    }
+
+    pub fn abandon(&self, lease: Lease) -> Result<(), ClientError> {
+        self.call(Request::Abandon(lease))
+    }
+
+    pub fn peek_next(&self) -> Result<Option<TicketId>, ClientError> {
+        self.call(Request::PeekNext)
+    }
+
+    pub fn claim_next(&self, assignee: String) -> Result<Option<TicketId>, ClientError> {
+        self.call(Request::ClaimNext(assignee))
+    }
+
+    pub fn delete(&self, id: TicketId) -> Result<Option<Ticket>, ClientError> {
+        self.call(Request::Delete(id))
+    }
+
+    pub fn list(&self) -> Result<Vec<Ticket>, ClientError> {
+        self.call(Request::List)
+    }
+
+    fn call<T: Decode>(&self, request: Request) -> Result<T, ClientError> {
+        let mut connection = self.connection.lock().unwrap();
+        let outcome = self.exchange(&mut connection, &wire::to_bytes(&request));
+        let response = outcome.map_err(|e| {
+            // We don't know where the stream stands: a late response could be read
+            // as the response to the next request. Start afresh.
+            *connection = None;
+            match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                    ClientError::TimedOut(self.timeout)
+                }
+                _ => ClientError::Disconnected,
+            }
+        })?;
+        wire::from_bytes::<Result<T, ClientError>>(&response).map_err(|_| {
+            *connection = None;
+            ClientError::Disconnected
+        })?
+    }
+
+    fn exchange(&self, connection: &mut Option<UnixStream>, request: &[u8]) -> io::Result<Vec<u8>> {
+        if connection.is_none() {
+            *connection = Some(UnixStream::connect(&self.path)?);
+        }
+        let stream = connection.as_mut().unwrap();
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        wire::write_frame(stream, request)?;
+        wire::read_frame(stream)
+    }
+}
@@ -0,0 +1,156 @@
+// The order in which the server processes queued commands.
+//
+// Commands are split into two lanes: reads (which don't change the store) and writes.
+// Clients pick the lane when they send a command, so that reads don't queue up behind writes.
+// The policy picks the lane the next command comes from; within a lane, it's first come,
+// first served. With read priority, a steady stream of reads could keep writes waiting
+// forever: the starvation guard lets a write through once `starvation_limit` reads
+// have jumped ahead of it.
+//
+// The policy can only choose among the commands it has taken out of the lanes: the lookahead.
+// These are on top of the commands the lanes themselves hold.
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+use std::time::Instant;
+
+use actor::Scheduler;
+
+use crate::metrics::CommandKind;
+use crate::Command;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchedulingPolicy {
+    // In arrival order, whatever the lane.
+    #[default]
+    Fifo,
+    // Reads go first, as long as the starvation guard allows it.
+    ReadPriority,
+    // Out of every `reads + writes` commands, `reads` come from the read lane
+    // and `writes` from the write lane, when both have commands waiting.
+    WeightedFair {
+        reads: NonZeroU32,
+        writes: NonZeroU32,
+    },
+}
+
+impl SchedulingPolicy {
+    // Panics if either weight is zero: that lane would never get a turn.
+    pub fn weighted_fair(reads: u32, writes: u32) -> Self {
+        match (NonZeroU32::new(reads), NonZeroU32::new(writes)) {
+            (Some(reads), Some(writes)) => SchedulingPolicy::WeightedFair { reads, writes },
+            _ => panic!("Weights must be at least one"),
+        }
+    }
+}
+
+pub(crate) struct Lanes {
+    policy: SchedulingPolicy,
+    starvation_limit: usize,
+    // How many queued commands the policy gets to choose from.
+    lookahead: usize,
+    // Commands are tagged with the time they were sent: they're taken out of
+    // the two lanes in turn, not in the order they arrived in.
+    reads: VecDeque<(Instant, Command)>,
+    writes: VecDeque<(Instant, Command)>,
+    // How many reads have jumped ahead of the oldest waiting write.
+    bypassed: usize,
+    // What's left of the current weighted fair round.
+    credits: (u32, u32),
+}
+
+impl Lanes {
+    pub(crate) fn new(policy: SchedulingPolicy, starvation_limit: usize, lookahead: usize) -> Self {
+        Self {
+            policy,
+            starvation_limit,
+            // In FIFO order, there's nothing to choose from.
+            lookahead: match policy {
+                SchedulingPolicy::Fifo => 1,
+                _ => lookahead.max(1),
+            },
+            reads: VecDeque::new(),
+            writes: VecDeque::new(),
+            bypassed: 0,
+            credits: (0, 0),
+        }
+    }
+
+    pub(crate) fn lookahead(&self) -> usize {
+        self.lookahead
+    }
+
+    fn next_is_read(&mut self) -> bool {
+        let (Some((read, _)), Some((write, _))) = (self.reads.front(), self.writes.front()) else {
+            return !self.reads.is_empty();
+        };
+        if self.bypassed >= self.starvation_limit {
+            return false;
+        }
+        match self.policy {
+            SchedulingPolicy::Fifo => read < write,
+            SchedulingPolicy::ReadPriority => true,
+            SchedulingPolicy::WeightedFair { reads, writes } => {
+                if self.credits == (0, 0) {
+                    self.credits = (reads.get(), writes.get());
+                }
+                if self.credits.0 > 0 {
+                    self.credits.0 -= 1;
+                    true
+                } else {
+                    self.credits.1 = self.credits.1.saturating_sub(1);
+                    false
+                }
+            }
+        }
+    }
+}
+
+impl Scheduler<Command> for Lanes {
+    fn push(&mut self, command: Command) {
+        // The shutdown signal is the only command without a context: it comes last anyway.
+        let sent = command
+            .context()
+            .map_or_else(Instant::now, |context| context.enqueued_at());
+        let entry = (sent, command);
+        if command_is_read(&entry.1) {
+            self.reads.push_back(entry);
+        } else {
+            self.writes.push_back(entry);
+        }
+    }
+
+    fn pop(&mut self) -> Option<Command> {
+        if self.next_is_read() {
+            let (arrival, command) = self.reads.pop_front()?;
+            if self
+                .writes
+                .front()
+                .is_some_and(|(write, _)| *write < arrival)
+            {
+                self.bypassed += 1;
+            }
+            Some(command)
+        } else {
+            self.bypassed = 0;
+            self.writes.pop_front().map(|(_, command)| command)
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.reads.len() + self.writes.len()
+    }
+}
+
+// The shutdown signal goes in the write lane: it doesn't jump ahead of anything.
+pub(crate) fn command_is_read(command: &Command) -> bool {
+    matches!(
+        command.kind(),
+        Some(
+            CommandKind::Get
+                | CommandKind::GetMany
+                | CommandKind::PeekNext
+                | CommandKind::List
+                | CommandKind::Subscribe
+        )
+    )
+}
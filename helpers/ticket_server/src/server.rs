@@ -0,0 +1,289 @@
+// The server thread: it owns the store and processes commands one at a time.
+//
+// If the server is supervised, a panic while processing a command doesn't kill the thread.
+// The supervisor catches it and rebuilds the store from the last checkpoint, replaying
+// every command that was acknowledged since then. The receiving end of the channel
+// survives the panic, so clients keep working across restarts.
+use std::any::Any;
+use std::ops::ControlFlow;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use actor::{Actor, Mailbox};
+use tracing::Span;
+
+use crate::clock::{Clock, ManualClock};
+use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::leases::Lease;
+use crate::metrics::Metrics;
+use crate::scheduling::Lanes;
+use crate::shutdown::{Persistence, ShutdownReport};
+use crate::storage::Storage;
+use crate::store::{Checkpoint, TicketId, TicketStore};
+use crate::trace;
+use crate::Command;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SupervisorStatus {
+    pub restarts: u64,
+    pub last_panic: Option<String>,
+}
+
+pub(crate) struct Server {
+    store: TicketStore,
+    clock: Arc<dyn Clock>,
+    // The store reads the time from here: we set it before processing each command,
+    // so that replaying a command later produces exactly the same timestamps.
+    now: ManualClock,
+    commands_processed: u64,
+    recovery: Option<Recovery>,
+    metrics: Arc<Metrics>,
+}
+
+struct Recovery {
+    checkpoint: Checkpoint,
+    // Commands that mutated the store since `checkpoint` was taken, oldest first.
+    journal: Vec<(SystemTime, Mutation)>,
+    checkpoint_every: usize,
+}
+
+// The part of a `Command` that's needed to replay it.
+#[derive(Clone)]
+enum Mutation {
+    Insert(TicketDraft),
+    Update(TicketPatch),
+    InsertMany(Vec<TicketDraft>),
+    PatchMany(Vec<TicketPatch>),
+    Checkout(TicketId, String, Duration),
+    Renew(Lease, Duration),
+    Commit(Lease, TicketPatch),
+    Abandon(Lease),
+    ClaimNext(String),
+    Delete(TicketId),
+    // A ticket, as it was after an edit made through its handle.
+    Overwrite(Ticket),
+}
+
+impl Mutation {
+    fn of(command: &Command) -> Option<Self> {
+        match command {
+            Command::Insert { draft, .. } => Some(Mutation::Insert(draft.clone())),
+            Command::Update { patch, .. } => Some(Mutation::Update(patch.clone())),
+            Command::InsertMany { drafts, .. } => Some(Mutation::InsertMany(drafts.clone())),
+            Command::PatchMany { patches, .. } => Some(Mutation::PatchMany(patches.clone())),
+            Command::Checkout {
+                id,
+                holder,
+                duration,
+                ..
+            } => Some(Mutation::Checkout(*id, holder.clone(), *duration)),
+            Command::Renew {
+                lease, duration, ..
+            } => Some(Mutation::Renew(lease.clone(), *duration)),
+            Command::Commit { lease, patch, .. } => {
+                Some(Mutation::Commit(lease.clone(), patch.clone()))
+            }
+            Command::Abandon { lease, .. } => Some(Mutation::Abandon(lease.clone())),
+            Command::ClaimNext { assignee, .. } => Some(Mutation::ClaimNext(assignee.clone())),
+            Command::Delete { id, .. } => Some(Mutation::Delete(*id)),
+            Command::Get { .. }
+            | Command::GetMany { .. }
+            | Command::PeekNext { .. }
+            | Command::List { .. }
+            | Command::Subscribe { .. }
+            | Command::Shutdown => None,
+        }
+    }
+
+    fn apply(self, store: &mut TicketStore) {
+        match self {
+            Mutation::Insert(draft) => {
+                store.add_ticket(draft);
+            }
+            // Failures were already reported to the client the first time around.
+            Mutation::Update(patch) => {
+                let _ = store.update(patch);
+            }
+            Mutation::InsertMany(drafts) => {
+                for draft in drafts {
+                    store.add_ticket(draft);
+                }
+            }
+            Mutation::PatchMany(patches) => {
+                for patch in patches {
+                    let _ = store.update(patch);
+                }
+            }
+            Mutation::Checkout(id, holder, duration) => {
+                let _ = store.checkout(id, holder, duration);
+            }
+            Mutation::Renew(lease, duration) => {
+                let _ = store.renew(&lease, duration);
+            }
+            Mutation::Commit(lease, patch) => {
+                let _ = store.commit(&lease, patch);
+            }
+            Mutation::Abandon(lease) => {
+                let _ = store.abandon(&lease);
+            }
+            Mutation::ClaimNext(assignee) => {
+                let _ = store.claim_next(assignee);
+            }
+            Mutation::Delete(id) => {
+                let _ = store.delete(id);
+            }
+            Mutation::Overwrite(ticket) => store.overwrite(ticket),
+        }
+    }
+}
+
+impl Server {
+    // `checkpoint_every` is `Some` if the server is supervised.
+    pub(crate) fn new(
+        clock: Arc<dyn Clock>,
+        checkpoint_every: Option<usize>,
+        storage: Box<dyn Storage>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        let now = ManualClock::default();
+        let mut store = TicketStore::with_clock(now.clone()).storing_in(storage);
+        if checkpoint_every.is_some() {
+            store = store.recording_edits();
+        }
+        let recovery = checkpoint_every.map(|checkpoint_every| Recovery {
+            checkpoint: store.checkpoint(),
+            journal: Vec::new(),
+            checkpoint_every,
+        });
+        Self {
+            store,
+            clock,
+            now,
+            commands_processed: 0,
+            recovery,
+            metrics,
+        }
+    }
+
+    fn process(&mut self, command: Command) {
+        let Some(kind) = command.kind() else {
+            return;
+        };
+        self.metrics.dequeued();
+        let at = self.clock.now();
+        self.now.set(at);
+        let mutation = self.recovery.as_ref().and_then(|_| Mutation::of(&command));
+        if self.recovery.is_some() {
+            self.store.sync_edits();
+        }
+        let span = command
+            .context()
+            .map_or_else(Span::none, |context| context.server_span(kind));
+        let _entered = span.enter();
+        let start = Instant::now();
+        crate::handle(&mut self.store, command);
+        let elapsed = start.elapsed();
+        span.record("handling_us", trace::micros(elapsed));
+        self.metrics.processed(kind, elapsed);
+        self.commands_processed += 1;
+
+        // The command went through: from now on, it must survive a restart.
+        // So must the handle edits the store caught up with on the way, which came before it.
+        if let Some(recovery) = &mut self.recovery {
+            recovery.journal_edits(&mut self.store, at);
+            recovery
+                .journal
+                .extend(mutation.map(|mutation| (at, mutation)));
+            if recovery.journal.len() >= recovery.checkpoint_every {
+                recovery.checkpoint = self.store.checkpoint();
+                recovery.journal.clear();
+            }
+        }
+    }
+
+    // Rebuilds the store after a panic. Returns `false` if the server isn't supervised.
+    fn recover(&mut self) -> bool {
+        let Some(recovery) = &mut self.recovery else {
+            return false;
+        };
+        // Clients may have edited tickets through their handles since we last looked:
+        // the rebuilt tickets would lose those changes otherwise.
+        self.store.sync_edits();
+        recovery.journal_edits(&mut self.store, self.now.now());
+        let now = &self.now;
+        self.store.silently(|store| {
+            store.restore(&recovery.checkpoint, |store| {
+                for (at, mutation) in &recovery.journal {
+                    now.set(*at);
+                    mutation.clone().apply(store);
+                }
+            });
+        });
+        true
+    }
+}
+
+impl Recovery {
+    fn journal_edits(&mut self, store: &mut TicketStore, at: SystemTime) {
+        let edits = store.take_edits().into_iter();
+        self.journal
+            .extend(edits.map(|ticket| (at, Mutation::Overwrite(ticket))));
+    }
+}
+
+impl Actor for Server {
+    type Message = Command;
+
+    fn handle(&mut self, command: Command) -> ControlFlow<()> {
+        if let Command::Shutdown = command {
+            // Commands that were accepted right before the shutdown started
+            // may have been queued after the shutdown signal: the mailbox processes them too.
+            return ControlFlow::Break(());
+        }
+        self.process(command);
+        ControlFlow::Continue(())
+    }
+}
+
+// Runs the server until it's asked to shut down or every client has gone away.
+// Commands are picked from `lanes`, which survive panics along with the mailbox.
+pub(crate) fn run(
+    mailbox: Mailbox<Command>,
+    mut lanes: Lanes,
+    mut server: Server,
+    persistence: Option<Box<dyn Persistence>>,
+    status: Arc<Mutex<SupervisorStatus>>,
+) -> ShutdownReport {
+    loop {
+        match catch_unwind(AssertUnwindSafe(|| {
+            let lookahead = lanes.lookahead();
+            mailbox.run_scheduled(&mut server, &mut lanes, lookahead)
+        })) {
+            Ok(()) => break,
+            Err(panic) => {
+                if !server.recover() {
+                    resume_unwind(panic);
+                }
+                let mut status = status.lock().unwrap();
+                status.restarts += 1;
+                status.last_panic = Some(panic_message(&*panic));
+            }
+        }
+    }
+    let flushed = persistence.map(|mut persistence| persistence.flush(&server.store.snapshot()));
+    ShutdownReport {
+        commands_processed: server.commands_processed,
+        flushed,
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
@@ -0,0 +1,81 @@
+use std::io;
+use std::ops::Deref;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::data::Ticket;
+use crate::server::SupervisorStatus;
+use crate::{Command, TicketStoreClient};
+
+// Where the state of the store ends up when the server shuts down.
+pub trait Persistence: Send {
+    fn flush(&mut self, tickets: &[Ticket]) -> io::Result<()>;
+}
+
+impl<F> Persistence for F
+where
+    F: FnMut(&[Ticket]) -> io::Result<()> + Send,
+{
+    fn flush(&mut self, tickets: &[Ticket]) -> io::Result<()> {
+        self(tickets)
+    }
+}
+
+#[derive(Debug)]
+pub struct ShutdownReport {
+    pub commands_processed: u64,
+    // `None` if no persistence hook was configured.
+    pub flushed: Option<io::Result<()>>,
+}
+
+// Returned by `launch`: it owns the server thread.
+// It dereferences to a `TicketStoreClient`, so it can be used to talk to the server directly.
+pub struct ServerHandle {
+    client: TicketStoreClient,
+    thread: JoinHandle<ShutdownReport>,
+    status: Arc<Mutex<SupervisorStatus>>,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(
+        client: TicketStoreClient,
+        thread: JoinHandle<ShutdownReport>,
+        status: Arc<Mutex<SupervisorStatus>>,
+    ) -> Self {
+        Self {
+            client,
+            thread,
+            status,
+        }
+    }
+
+    // How many times the supervisor had to restart the server, and why it last did.
+    // Always empty for servers that aren't supervised.
+    pub fn supervisor_status(&self) -> SupervisorStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn client(&self) -> TicketStoreClient {
+        self.client.clone()
+    }
+
+    // Stops accepting new commands, waits for the queued ones to be processed,
+    // flushes the store through the persistence hook and returns once the server thread is gone.
+    // Returns an error if the server thread panicked.
+    pub fn shutdown(self) -> std::thread::Result<ShutdownReport> {
+        self.client.accepting.store(false, Ordering::Release);
+        // A blocking send: the shutdown signal must get through even if the queue is full.
+        // If it fails, the server is already gone and `join` will tell us why.
+        let _ = self.client.writes.send(Command::Shutdown);
+        self.thread.join()
+    }
+}
+
+impl Deref for ServerHandle {
+    type Target = TicketStoreClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
@@ -0,0 +1,141 @@
+// Where `TicketStore` keeps its tickets, and how it hands out their ids.
+//
+// `MapStorage` is the original layout: a `BTreeMap` from sequential ids to tickets,
+// each in its own allocation. `ArenaStorage` (see `arena.rs`) packs tickets inline in
+// fixed-size chunks, addressed by generational ids. The store, and the server on top of it,
+// work the same with either: pick one with `TicketStore::with_storage` or `ServerBuilder::storage`.
+use std::collections::BTreeMap;
+
+use crate::arena::Place;
+use crate::data::Ticket;
+use crate::store::TicketId;
+use crate::sync;
+
+pub trait Storage: Send {
+    // An empty storage, handing out ids from `first_id` onwards.
+    fn starting_at(first_id: u64) -> Self
+    where
+        Self: Sized;
+
+    // The id the next ticket will be stored under.
+    fn next_id(&self) -> TicketId;
+
+    // Stores the ticket under its id, which must be `next_id()`.
+    fn insert(&mut self, ticket: Ticket);
+
+    fn get(&self, id: TicketId) -> Option<&sync::Mutex<Ticket>>;
+
+    // Where the ticket lives, for a handle to share.
+    fn cell(&self, id: TicketId) -> Option<TicketCell>;
+
+    // Keeps the ticket in `cell` from now on, so that the handles sharing it
+    // point to our ticket. `cell` must come from the same kind of storage.
+    fn adopt(&mut self, id: TicketId, cell: TicketCell);
+
+    fn remove(&mut self, id: TicketId) -> Option<TicketCell>;
+
+    // Every ticket id, in order.
+    fn ids(&self) -> Vec<TicketId>;
+
+    // A storage with the same layout, sharing our tickets.
+    fn share(&self) -> Box<dyn Storage>;
+
+    // A storage with the same layout, holding copies of our tickets:
+    // ids it hands out from then on are the same we would.
+    fn copy(&self) -> Box<dyn Storage>;
+}
+
+// Where a ticket lives, shared between its storage and the handles to it.
+#[derive(Clone)]
+pub struct TicketCell(pub(crate) Cell);
+
+#[derive(Clone)]
+pub(crate) enum Cell {
+    Boxed(sync::Arc<sync::Mutex<Ticket>>),
+    Arena(Place),
+}
+
+impl TicketCell {
+    pub(crate) fn mutex(&self) -> &sync::Mutex<Ticket> {
+        match &self.0 {
+            Cell::Boxed(ticket) => ticket,
+            Cell::Arena(place) => place.mutex(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MapStorage {
+    tickets: BTreeMap<TicketId, sync::Arc<sync::Mutex<Ticket>>>,
+    counter: u64,
+}
+
+impl Default for MapStorage {
+    fn default() -> Self {
+        Self::starting_at(0)
+    }
+}
+
+impl Storage for MapStorage {
+    fn starting_at(first_id: u64) -> Self {
+        Self {
+            tickets: BTreeMap::new(),
+            counter: first_id,
+        }
+    }
+
+    fn next_id(&self) -> TicketId {
+        TicketId(self.counter)
+    }
+
+    fn insert(&mut self, ticket: Ticket) {
+        debug_assert_eq!(ticket.id, self.next_id());
+        self.tickets
+            .insert(ticket.id, sync::Arc::new(sync::Mutex::new(ticket)));
+        self.counter += 1;
+    }
+
+    fn get(&self, id: TicketId) -> Option<&sync::Mutex<Ticket>> {
+        self.tickets.get(&id).map(|ticket| &**ticket)
+    }
+
+    fn cell(&self, id: TicketId) -> Option<TicketCell> {
+        let ticket = self.tickets.get(&id)?;
+        Some(TicketCell(Cell::Boxed(ticket.clone())))
+    }
+
+    fn adopt(&mut self, id: TicketId, cell: TicketCell) {
+        let Cell::Boxed(ticket) = cell.0 else {
+            unreachable!("A map storage only adopts boxed tickets")
+        };
+        self.tickets.insert(id, ticket);
+    }
+
+    fn remove(&mut self, id: TicketId) -> Option<TicketCell> {
+        let ticket = self.tickets.remove(&id)?;
+        Some(TicketCell(Cell::Boxed(ticket)))
+    }
+
+    fn ids(&self) -> Vec<TicketId> {
+        self.tickets.keys().copied().collect()
+    }
+
+    fn share(&self) -> Box<dyn Storage> {
+        Box::new(self.clone())
+    }
+
+    fn copy(&self) -> Box<dyn Storage> {
+        let copy = |ticket: &sync::Mutex<Ticket>| {
+            let ticket = ticket.lock().unwrap().clone();
+            sync::Arc::new(sync::Mutex::new(ticket))
+        };
+        Box::new(Self {
+            tickets: self
+                .tickets
+                .iter()
+                .map(|(id, ticket)| (*id, copy(ticket)))
+                .collect(),
+            counter: self.counter,
+        })
+    }
+}
@@ -0,0 +1,520 @@
+use crate::clock::{Clock, SystemClock};
+use crate::data::{Priority, Status, StatusChange, Ticket, TicketDraft, TicketPatch};
+use crate::events::{Field, Observers, TicketEvent};
+use crate::leases::{EditError, Lease, Leases};
+use crate::locking::LockSet;
+use crate::queue::WorkQueue;
+use crate::storage::{MapStorage, Storage, TicketCell};
+use crate::sync;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, LockResult, PoisonError, TryLockError};
+use std::time::{Duration, SystemTime};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TicketId(pub(crate) u64);
+
+// A shared, lockable reference to a ticket: see `TicketStore::get`.
+#[derive(Clone)]
+pub struct TicketHandle {
+    id: TicketId,
+    ticket: TicketCell,
+    // Shared with every store holding the ticket and with every other handle they gave out.
+    edited: EditNotices,
+}
+
+// Tickets that were locked through a handle since each store last caught up with them,
+// keyed by store. Clones of a store share their tickets, so they share this as well,
+// but each of them keeps its own set: one of them catching up doesn't hide an edit from the others.
+#[derive(Clone, Default)]
+struct EditNotices(sync::Arc<sync::Mutex<BTreeMap<u64, BTreeSet<TicketId>>>>);
+
+impl EditNotices {
+    fn notify(&self, id: TicketId) {
+        for edited in self.0.lock().unwrap().values_mut() {
+            edited.insert(id);
+        }
+    }
+}
+
+// A store's own set in the `EditNotices` it shares with its clones.
+struct EditReader {
+    notices: EditNotices,
+    key: u64,
+}
+
+impl EditReader {
+    fn new() -> Self {
+        Self::register(EditNotices::default(), BTreeSet::new())
+    }
+
+    fn register(notices: EditNotices, edited: BTreeSet<TicketId>) -> Self {
+        let mut sets = notices.0.lock().unwrap();
+        let key = sets.last_key_value().map_or(0, |(key, _)| key + 1);
+        sets.insert(key, edited);
+        drop(sets);
+        Self { notices, key }
+    }
+
+    // A reader for a clone of the store, starting with a copy of this one's set.
+    fn fork(&self) -> Self {
+        let edited = self.notices.0.lock().unwrap()[&self.key].clone();
+        Self::register(self.notices.clone(), edited)
+    }
+
+    fn take(&self) -> BTreeSet<TicketId> {
+        let mut sets = self.notices.0.lock().unwrap();
+        std::mem::take(sets.get_mut(&self.key).unwrap())
+    }
+
+    // Puts the ticket back in the set, for the next time the store catches up.
+    fn defer(&self, id: TicketId) {
+        let mut sets = self.notices.0.lock().unwrap();
+        sets.get_mut(&self.key).unwrap().insert(id);
+    }
+}
+
+impl Drop for EditReader {
+    fn drop(&mut self) {
+        self.notices.0.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl TicketHandle {
+    pub fn id(&self) -> TicketId {
+        self.id
+    }
+
+    // Whoever locks the ticket may change it: the store takes another look at it
+    // the next time it catches up with handle edits (see `TicketStore::sync_edits`).
+    // Leases aren't enforced here: locking doesn't say who's writing, and the leases live
+    // in the store, which handle edits don't go through. A lease only turns away writes
+    // that go through the store: the holder's commit lands on top of what handles did meanwhile.
+    pub fn lock(&self) -> LockResult<sync::MutexGuard<'_, Ticket>> {
+        let guard = self.ticket.mutex().lock();
+        // Flagged while we hold the lock, so that the store can't look at the ticket
+        // in between and miss our changes.
+        self.edited.notify(self.id);
+        guard
+    }
+
+    // A copy of the ticket, for readers that won't change it: unlike `lock`,
+    // it doesn't make the store take another look at the ticket.
+    pub(crate) fn current(&self) -> Ticket {
+        self.ticket
+            .mutex()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+// Replaces the ticket, whatever state a panicking editor left it in.
+fn refill(cell: &sync::Mutex<Ticket>, ticket: Ticket) {
+    *cell.lock().unwrap_or_else(PoisonError::into_inner) = ticket;
+    sync::clear_poison(cell);
+}
+
+// A deep copy of the store's content, used to recover from a server crash.
+pub(crate) struct Checkpoint {
+    tickets: Box<dyn Storage>,
+    leases: Leases,
+}
+
+// Clones share the same tickets: a handle obtained from one of them
+// points to the same ticket as in the others.
+pub struct TicketStore {
+    tickets: Box<dyn Storage>,
+    clock: Arc<dyn Clock>,
+    queue: WorkQueue,
+    observers: Observers,
+    leases: Leases,
+    edited: EditReader,
+    // Copies of the tickets `sync_edits` found, if someone asked for them: see `take_edits`.
+    edits: Option<Vec<Ticket>>,
+}
+
+impl TicketStore {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        Self {
+            tickets: Box::new(MapStorage::default()),
+            clock: Arc::new(clock),
+            queue: WorkQueue::new(),
+            observers: Observers::default(),
+            leases: Leases::default(),
+            edited: EditReader::new(),
+            edits: None,
+        }
+    }
+
+    // Keeps tickets in `storage`, rather than in a `MapStorage`.
+    // Meant to be called on an empty store: tickets that are already in it are dropped.
+    pub fn with_storage<S: Storage + 'static>(self, storage: S) -> Self {
+        self.storing_in(Box::new(storage))
+    }
+
+    pub(crate) fn storing_in(self, storage: Box<dyn Storage>) -> Self {
+        Self {
+            tickets: storage,
+            ..self
+        }
+    }
+
+    // Keeps a copy of every handle edit the store catches up with, until `take_edits`.
+    pub(crate) fn recording_edits(self) -> Self {
+        Self {
+            edits: Some(Vec::new()),
+            ..self
+        }
+    }
+
+    pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
+        let id = self.tickets.next_id();
+        let ticket = new_ticket(id, ticket, self.clock.now());
+        self.queue.track(&ticket);
+        self.tickets.insert(ticket);
+        self.observers.notify(vec![TicketEvent::Created { id }]);
+        id
+    }
+
+    // The `get` method should return a handle to the ticket
+    // which allows the caller to either read or modify the ticket.
+    // Changes made through the handle bypass the store, so they are not timestamped:
+    // use `update` if you want `updated_at` and `status_changes` to be kept up to date.
+    // The work queue does catch up with them, though, and so does the journal of a supervised server.
+    pub fn get(&self, id: TicketId) -> Option<TicketHandle> {
+        let ticket = self.tickets.cell(id)?;
+        Some(self.handle(id, ticket))
+    }
+
+    // Handles on all the given tickets, to be locked together: see `LockSet`.
+    pub fn lock_set(&self, ids: &[TicketId]) -> Result<LockSet, EditError> {
+        let tickets = ids
+            .iter()
+            .map(|id| {
+                self.get(*id)
+                    .map(|ticket| (*id, ticket))
+                    .ok_or(EditError::UnknownTicket(*id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LockSet::new(tickets))
+    }
+
+    // A copy of every ticket, ordered by id.
+    pub fn snapshot(&self) -> Vec<Ticket> {
+        self.tickets
+            .ids()
+            .into_iter()
+            .map(|id| self.lock_inner(id).clone())
+            .collect()
+    }
+
+    // Fails if the ticket doesn't exist, or if someone else holds a lease on it.
+    pub fn update(&mut self, patch: TicketPatch) -> Result<(), EditError> {
+        if self.tickets.get(patch.id).is_none() {
+            return Err(EditError::UnknownTicket(patch.id));
+        }
+        self.leases.check(patch.id, None, self.clock.now())?;
+        self.apply(patch);
+        Ok(())
+    }
+
+    // Grants `holder` exclusive edit rights on the ticket for `duration`.
+    // Until the lease expires, or is committed or abandoned, every other write through
+    // the store fails. Handles are another matter: see `TicketHandle::lock`.
+    pub fn checkout(
+        &mut self,
+        id: TicketId,
+        holder: String,
+        duration: Duration,
+    ) -> Result<Lease, EditError> {
+        if self.tickets.get(id).is_none() {
+            return Err(EditError::UnknownTicket(id));
+        }
+        let now = self.clock.now();
+        self.leases.grant(id, holder, now + duration, now)
+    }
+
+    // Moves the expiry of the lease to `duration` from now.
+    pub fn renew(&mut self, lease: &Lease, duration: Duration) -> Result<Lease, EditError> {
+        let now = self.clock.now();
+        self.leases.extend(lease, now + duration, now)
+    }
+
+    // Applies the patch and releases the lease.
+    // The patch always targets the leased ticket, whatever its `id` says.
+    pub fn commit(&mut self, lease: &Lease, patch: TicketPatch) -> Result<(), EditError> {
+        if self.tickets.get(lease.id).is_none() {
+            return Err(EditError::UnknownTicket(lease.id));
+        }
+        self.leases.release(lease, self.clock.now())?;
+        self.apply(TicketPatch {
+            id: lease.id,
+            ..patch
+        });
+        Ok(())
+    }
+
+    // Releases the lease without changing the ticket.
+    pub fn abandon(&mut self, lease: &Lease) -> Result<(), EditError> {
+        self.leases.release(lease, self.clock.now())
+    }
+
+    fn apply(&mut self, patch: TicketPatch) {
+        let mut ticket = self.tickets.get(patch.id).unwrap().lock().unwrap();
+        let events = apply_patch(&mut ticket, patch, self.clock.now());
+        if !events.is_empty() {
+            self.queue.track(&ticket);
+        }
+        drop(ticket);
+        self.observers.notify(events);
+    }
+
+    // Removes the ticket from the store, unless someone else holds a lease on it.
+    // Outstanding handles stay valid, but they're no longer reachable through the store.
+    pub fn delete(&mut self, id: TicketId) -> Result<Option<TicketHandle>, EditError> {
+        if self.tickets.get(id).is_none() {
+            return Ok(None);
+        }
+        self.leases.check(id, None, self.clock.now())?;
+        let ticket = self
+            .tickets
+            .remove(id)
+            .map(|ticket| self.handle(id, ticket));
+        self.leases.forget(id);
+        self.observers.notify(vec![TicketEvent::Deleted { id }]);
+        Ok(ticket)
+    }
+
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            tickets: self.tickets.copy(),
+            leases: self.leases.clone(),
+        }
+    }
+
+    // Replaces every ticket with the ones in the checkpoint, then lets `replay` bring them
+    // up to date. Tickets that were already in the store are refilled in place, so that
+    // the handles it gave out before still point to it. Observers and the clock are left untouched.
+    pub(crate) fn restore<F: FnOnce(&mut Self)>(&mut self, checkpoint: &Checkpoint, replay: F) {
+        let restored = checkpoint.tickets.copy();
+        let previous = std::mem::replace(&mut self.tickets, restored);
+        self.queue = WorkQueue::new();
+        for ticket in self.snapshot() {
+            self.queue.track(&ticket);
+        }
+        self.leases = checkpoint.leases.clone();
+        replay(self);
+
+        for id in self.tickets.ids() {
+            let Some(previous) = previous.cell(id) else {
+                continue;
+            };
+            refill(previous.mutex(), self.lock_inner(id).clone());
+            self.tickets.adopt(id, previous);
+        }
+    }
+
+    // Replaces a ticket wholesale, e.g. to replay an edit made through its handle.
+    pub(crate) fn overwrite(&mut self, ticket: Ticket) {
+        let Some(cell) = self.tickets.get(ticket.id) else {
+            return;
+        };
+        self.queue.track(&ticket);
+        *cell.lock().unwrap() = ticket;
+    }
+
+    // Runs `f` without notifying observers of the changes it makes.
+    pub(crate) fn silently<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        let observers = std::mem::take(&mut self.observers);
+        f(self);
+        self.observers = observers;
+    }
+
+    // Catches up with the changes made through handles since the last call.
+    // Tickets that are still locked are looked at next time.
+    pub fn sync_edits(&mut self) {
+        let edited = self.edited.take();
+        for id in edited {
+            let Some(cell) = self.tickets.get(id) else {
+                continue;
+            };
+            let (ticket, complete) = match cell.try_lock() {
+                Ok(ticket) => (ticket, true),
+                // The edit was cut short by a panic: it isn't worth keeping.
+                Err(TryLockError::Poisoned(poisoned)) => (poisoned.into_inner(), false),
+                Err(TryLockError::WouldBlock) => {
+                    self.edited.defer(id);
+                    continue;
+                }
+            };
+            self.queue.track(&ticket);
+            if let (Some(edits), true) = (&mut self.edits, complete) {
+                edits.push(ticket.clone());
+            }
+        }
+    }
+
+    // The tickets `sync_edits` found since the last call, as it found them, oldest first.
+    // Always empty unless the store is `recording_edits`.
+    pub(crate) fn take_edits(&mut self) -> Vec<Ticket> {
+        self.edits.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // The next ticket someone should pick up, if there's any.
+    // Tickets someone holds a lease on can't be claimed: they're passed over.
+    pub fn peek_next(&mut self) -> Option<TicketId> {
+        self.sync_edits();
+        let now = self.clock.now();
+        let tickets = &self.tickets;
+        let leases = &mut self.leases;
+        self.queue.peek_next(
+            |id| tickets.get(id).map(|t| t.lock().unwrap().clone()),
+            |id| leases.check(id, None, now).is_ok(),
+        )
+    }
+
+    // Assigns the next ticket to `assignee` and moves it to `InProgress`.
+    // Leased tickets stay in the queue, to be claimed once the lease is over.
+    pub fn claim_next(&mut self, assignee: String) -> Option<TicketId> {
+        let id = self.peek_next()?;
+        let mut ticket = self.lock_inner(id);
+        let now = self.clock.now();
+        ticket.assignee = Some(assignee);
+        ticket.updated_at = now;
+        let mut events = vec![TicketEvent::FieldChanged {
+            id,
+            field: Field::Assignee,
+        }];
+        events.extend(transition(&mut ticket, Status::InProgress, now));
+        drop(ticket);
+        self.observers.notify(events);
+        Some(id)
+    }
+
+    // `callback` is invoked, on the thread that performed the mutation,
+    // for every event emitted from now on.
+    pub fn on_event<F>(&mut self, callback: F)
+    where
+        F: Fn(&TicketEvent) + Send + Sync + 'static,
+    {
+        self.observers.on_event(callback);
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<TicketEvent> {
+        self.observers.subscribe()
+    }
+
+    fn handle(&self, id: TicketId, ticket: TicketCell) -> TicketHandle {
+        TicketHandle {
+            id,
+            ticket,
+            edited: self.edited.notices.clone(),
+        }
+    }
+
+    // For the store's own use: the store keeps track of its own changes.
+    fn lock_inner(&self, id: TicketId) -> sync::MutexGuard<'_, Ticket> {
+        self.tickets.get(id).unwrap().lock().unwrap()
+    }
+}
+
+impl Clone for TicketStore {
+    fn clone(&self) -> Self {
+        Self {
+            tickets: self.tickets.share(),
+            clock: self.clock.clone(),
+            queue: self.queue.clone(),
+            observers: self.observers.clone(),
+            leases: self.leases.clone(),
+            edited: self.edited.fork(),
+            edits: self.edits.clone(),
+        }
+    }
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn new_ticket(id: TicketId, draft: TicketDraft, now: SystemTime) -> Ticket {
+    Ticket {
+        id,
+        title: draft.title,
+        description: draft.description,
+        status: Status::ToDo,
+        priority: Priority::default(),
+        due_date: None,
+        assignee: None,
+        created_at: now,
+        updated_at: now,
+        status_changes: Vec::new(),
+    }
+}
+
+// Applies the patch and returns what actually changed.
+// `updated_at` is only bumped if the patch actually changed something.
+pub(crate) fn apply_patch(
+    ticket: &mut Ticket,
+    patch: TicketPatch,
+    now: SystemTime,
+) -> Vec<TicketEvent> {
+    let id = ticket.id;
+    let mut events = Vec::new();
+    let mut changed = |field: Field| events.push(TicketEvent::FieldChanged { id, field });
+    if let Some(title) = patch.title {
+        if ticket.title != title {
+            ticket.title = title;
+            changed(Field::Title);
+        }
+    }
+    if let Some(description) = patch.description {
+        if ticket.description != description {
+            ticket.description = description;
+            changed(Field::Description);
+        }
+    }
+    if let Some(priority) = patch.priority {
+        if ticket.priority != priority {
+            ticket.priority = priority;
+            changed(Field::Priority);
+        }
+    }
+    if let Some(due_date) = patch.due_date {
+        if ticket.due_date != Some(due_date) {
+            ticket.due_date = Some(due_date);
+            changed(Field::DueDate);
+        }
+    }
+    if let Some(status) = patch.status {
+        events.extend(transition(ticket, status, now));
+    }
+    if !events.is_empty() {
+        ticket.updated_at = now;
+    }
+    events
+}
+
+// Records the status change, if there is one.
+fn transition(ticket: &mut Ticket, to: Status, at: SystemTime) -> Option<TicketEvent> {
+    if ticket.status == to {
+        return None;
+    }
+    let change = StatusChange {
+        from: ticket.status,
+        to,
+        at,
+    };
+    ticket.status_changes.push(change);
+    ticket.status = to;
+    Some(TicketEvent::StatusTransitioned {
+        id: ticket.id,
+        change,
+    })
+}
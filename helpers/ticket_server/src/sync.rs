@@ -0,0 +1,17 @@
+// The primitives guarding ticket handles.
+//
+// Building with `RUSTFLAGS="--cfg loom"` swaps them for loom's, so that the model tests
+// in `tests/loom.rs` can explore every interleaving of the threads sharing a ticket.
+// Everything else (the mailbox, the clock, metrics...) keeps using `std::sync`.
+#[cfg(loom)]
+pub(crate) use loom::sync::{Arc, Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Arc, Mutex, MutexGuard};
+
+// Loom's mutexes are never poisoned.
+#[cfg(loom)]
+pub(crate) fn clear_poison<T>(_mutex: &Mutex<T>) {}
+#[cfg(not(loom))]
+pub(crate) fn clear_poison<T>(mutex: &Mutex<T>) {
+    mutex.clear_poison();
+}
@@ -0,0 +1,245 @@
+// Following a request from the client to the server thread, and back.
+//
+// Every request gets a correlation id, tagged on the client's span. The id travels to the server
+// inside the `Command`, along with the client span itself: the server processes the command in a
+// span of its own, which follows from the client's and records how long the command sat in the
+// queue and how long it took to handle.
+//
+// `SpanRecorder` is a layer that keeps spans in memory, so that tests can look at them.
+// Put it on top of a `tracing_subscriber::Registry`, which keeps track of the spans themselves.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{field, info_span, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::metrics::CommandKind;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+// What the server needs to trace a request back to the client that sent it.
+pub(crate) struct RequestContext {
+    id: u64,
+    client_span: Span,
+    enqueued_at: Instant,
+}
+
+impl RequestContext {
+    // Allocates a new correlation id and tags the current span (the client's) with it.
+    pub(crate) fn new() -> Self {
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        let client_span = Span::current();
+        client_span.record("request_id", id);
+        Self {
+            id,
+            client_span,
+            enqueued_at: Instant::now(),
+        }
+    }
+
+    // When the client sent the request.
+    pub(crate) fn enqueued_at(&self) -> Instant {
+        self.enqueued_at
+    }
+
+    // The span the server processes the request in.
+    // `handling_us` is left for the server to record, once it's done.
+    pub(crate) fn server_span(&self, kind: CommandKind) -> Span {
+        let span = info_span!(
+            parent: None,
+            "process",
+            request_id = self.id,
+            command = kind.name(),
+            queue_wait_us = micros(self.enqueued_at.elapsed()),
+            handling_us = field::Empty,
+        );
+        span.follows_from(&self.client_span);
+        span
+    }
+}
+
+// The span a client waits for the server in.
+// The correlation id is filled in once the command is built.
+pub(crate) fn client_span() -> Span {
+    info_span!("request", request_id = field::Empty, command = field::Empty)
+}
+
+pub(crate) fn micros(duration: Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedSpan {
+    // Handed out in creation order, starting from 1.
+    pub id: u64,
+    pub name: &'static str,
+    pub parent: Option<u64>,
+    // The spans this one was linked to, with `Span::follows_from`.
+    pub follows_from: Vec<u64>,
+    pub fields: BTreeMap<&'static str, String>,
+}
+
+impl RecordedSpan {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+// Records spans, with their fields and links. Open spans are all kept, but only
+// the most recently closed ones are: a long-running process doesn't grow the recording forever.
+// Clones share the same recording.
+#[derive(Clone, Default)]
+pub struct SpanRecorder {
+    inner: Arc<Mutex<Recording>>,
+}
+
+struct Recording {
+    last_id: u64,
+    open: HashMap<u64, RecordedSpan>,
+    // Oldest first.
+    closed: VecDeque<RecordedSpan>,
+    capacity: usize,
+}
+
+// Our id for a span, kept in its extensions: the registry reuses its own ids once spans close.
+struct RecordedId(u64);
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            last_id: 0,
+            open: HashMap::new(),
+            closed: VecDeque::new(),
+            capacity: SpanRecorder::DEFAULT_CAPACITY,
+        }
+    }
+}
+
+impl SpanRecorder {
+    // How many closed spans are kept by default.
+    pub const DEFAULT_CAPACITY: usize = 10_000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keeps up to `capacity` closed spans, dropping the oldest ones first.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let recording = Recording {
+            capacity,
+            ..Recording::default()
+        };
+        Self {
+            inner: Arc::new(Mutex::new(recording)),
+        }
+    }
+
+    // Every span still in the recording, in creation order.
+    pub fn spans(&self) -> Vec<RecordedSpan> {
+        let recording = self.inner.lock().unwrap();
+        let open = recording.open.values();
+        let mut spans: Vec<_> = recording.closed.iter().chain(open).cloned().collect();
+        spans.sort_by_key(|span| span.id);
+        spans
+    }
+
+    pub fn spans_named(&self, name: &str) -> Vec<RecordedSpan> {
+        self.spans()
+            .into_iter()
+            .filter(|span| span.name == name)
+            .collect()
+    }
+
+    // Spans can only be updated while they're open.
+    fn update<S, F>(&self, id: &Id, ctx: &Context<'_, S>, f: F)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        F: FnOnce(&mut RecordedSpan),
+    {
+        let Some(id) = recorded_id(id, ctx) else {
+            return;
+        };
+        if let Some(span) = self.inner.lock().unwrap().open.get_mut(&id) {
+            f(span);
+        }
+    }
+}
+
+impl<S> Layer<S> for SpanRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| Some(parent.extensions().get::<RecordedId>()?.0));
+        let mut fields = BTreeMap::new();
+        attributes.record(&mut FieldVisitor(&mut fields));
+        let mut recording = self.inner.lock().unwrap();
+        recording.last_id += 1;
+        let recorded = RecordedSpan {
+            id: recording.last_id,
+            name: attributes.metadata().name(),
+            parent,
+            follows_from: Vec::new(),
+            fields,
+        };
+        span.extensions_mut().insert(RecordedId(recorded.id));
+        recording.open.insert(recorded.id, recorded);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.update(id, &ctx, |span| {
+            values.record(&mut FieldVisitor(&mut span.fields));
+        });
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<'_, S>) {
+        let Some(follows) = recorded_id(follows, &ctx) else {
+            return;
+        };
+        self.update(id, &ctx, |span| span.follows_from.push(follows));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(id) = recorded_id(&id, &ctx) else {
+            return;
+        };
+        let mut recording = self.inner.lock().unwrap();
+        let Some(span) = recording.open.remove(&id) else {
+            return;
+        };
+        recording.closed.push_back(span);
+        while recording.closed.len() > recording.capacity {
+            recording.closed.pop_front();
+        }
+    }
+}
+
+fn recorded_id<S>(id: &Id, ctx: &Context<'_, S>) -> Option<u64>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    Some(ctx.span(id)?.extensions().get::<RecordedId>()?.0)
+}
+
+struct FieldVisitor<'a>(&'a mut BTreeMap<&'static str, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
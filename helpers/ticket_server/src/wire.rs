@@ -0,0 +1,563 @@
+// A compact binary encoding for the values exchanged by `remote` clients and servers.
+//
+// Integers are big-endian, strings and sequences are prefixed with their length (`u32`),
+// enums and `Option`s with a one-byte tag. Every message travels in a frame:
+// a `u32` length followed by that many bytes.
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime};
+
+use ticket_fields::{TicketDescription, TicketTitle};
+
+use crate::data::{Priority, Status, StatusChange, Ticket, TicketDraft, TicketPatch};
+use crate::leases::{EditError, Lease};
+use crate::store::TicketId;
+use crate::ClientError;
+
+// Frames larger than this are rejected, rather than allocated.
+const MAX_FRAME: u32 = 64 * 1024 * 1024;
+
+pub(crate) fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(payload.len())
+        .ok()
+        .filter(|length| *length <= MAX_FRAME)
+        .ok_or_else(|| invalid("frame too large"))?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+pub(crate) fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length);
+    if length > MAX_FRAME {
+        return Err(invalid("frame too large"));
+    }
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+pub(crate) fn to_bytes<T: Encode>(value: &T) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    value.encode(&mut buffer);
+    buffer
+}
+
+// Fails if the bytes don't hold exactly one `T`.
+pub(crate) fn from_bytes<T: Decode>(mut bytes: &[u8]) -> io::Result<T> {
+    let value = T::decode(&mut bytes)?;
+    if !bytes.is_empty() {
+        return Err(invalid("trailing bytes"));
+    }
+    Ok(value)
+}
+
+pub(crate) fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+pub(crate) trait Encode {
+    fn encode(&self, buffer: &mut Vec<u8>);
+}
+
+pub(crate) trait Decode: Sized {
+    // Consumes the bytes it reads from the front of `input`.
+    fn decode(input: &mut &[u8]) -> io::Result<Self>;
+}
+
+fn take<'a>(input: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if input.len() < n {
+        return Err(invalid("unexpected end of message"));
+    }
+    let (taken, rest) = input.split_at(n);
+    *input = rest;
+    Ok(taken)
+}
+
+pub(crate) fn tag(input: &mut &[u8]) -> io::Result<u8> {
+    u8::decode(input)
+}
+
+impl Encode for u8 {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(*self);
+    }
+}
+
+impl Decode for u8 {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(take(input, 1)?[0])
+    }
+}
+
+impl Encode for u32 {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl Decode for u32 {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(u32::from_be_bytes(take(input, 4)?.try_into().unwrap()))
+    }
+}
+
+impl Encode for u64 {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl Decode for u64 {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(u64::from_be_bytes(take(input, 8)?.try_into().unwrap()))
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        (self.len() as u32).encode(buffer);
+        buffer.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.as_str().encode(buffer);
+    }
+}
+
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let length = u32::decode(input)? as usize;
+        let bytes = take(input, length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("invalid UTF-8"))
+    }
+}
+
+impl Encode for () {
+    fn encode(&self, _buffer: &mut Vec<u8>) {}
+}
+
+impl Decode for () {
+    fn decode(_input: &mut &[u8]) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            None => buffer.push(0),
+            Some(value) => {
+                buffer.push(1);
+                value.encode(buffer);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match tag(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            _ => Err(invalid("invalid option tag")),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        (self.len() as u32).encode(buffer);
+        for item in self {
+            item.encode(buffer);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let length = u32::decode(input)? as usize;
+        // Don't trust the length for the allocation: each item takes at least a byte.
+        let mut items = Vec::with_capacity(length.min(input.len()));
+        for _ in 0..length {
+            items.push(T::decode(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Encode, E: Encode> Encode for Result<T, E> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Ok(value) => {
+                buffer.push(0);
+                value.encode(buffer);
+            }
+            Err(error) => {
+                buffer.push(1);
+                error.encode(buffer);
+            }
+        }
+    }
+}
+
+impl<T: Decode, E: Decode> Decode for Result<T, E> {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match tag(input)? {
+            0 => Ok(Ok(T::decode(input)?)),
+            1 => Ok(Err(E::decode(input)?)),
+            _ => Err(invalid("invalid result tag")),
+        }
+    }
+}
+
+impl Encode for Duration {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.as_secs().encode(buffer);
+        self.subsec_nanos().encode(buffer);
+    }
+}
+
+impl Decode for Duration {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let secs = u64::decode(input)?;
+        let nanos = u32::decode(input)?;
+        if nanos >= 1_000_000_000 {
+            return Err(invalid("invalid duration"));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+// Times before the Unix epoch can't be encoded: they're clamped to it.
+impl Encode for SystemTime {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .encode(buffer);
+    }
+}
+
+impl Decode for SystemTime {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        SystemTime::UNIX_EPOCH
+            .checked_add(Duration::decode(input)?)
+            .ok_or_else(|| invalid("invalid time"))
+    }
+}
+
+impl Encode for TicketId {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.0.encode(buffer);
+    }
+}
+
+impl Decode for TicketId {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(TicketId(u64::decode(input)?))
+    }
+}
+
+impl Encode for TicketTitle {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.as_str().encode(buffer);
+    }
+}
+
+impl Decode for TicketTitle {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        String::decode(input)?
+            .try_into()
+            .map_err(|_| invalid("invalid title"))
+    }
+}
+
+impl Encode for TicketDescription {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.as_str().encode(buffer);
+    }
+}
+
+impl Decode for TicketDescription {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        String::decode(input)?
+            .try_into()
+            .map_err(|_| invalid("invalid description"))
+    }
+}
+
+impl Encode for Status {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            Status::ToDo => 0,
+            Status::InProgress => 1,
+            Status::Done => 2,
+        };
+        tag.encode(buffer);
+    }
+}
+
+impl Decode for Status {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match tag(input)? {
+            0 => Ok(Status::ToDo),
+            1 => Ok(Status::InProgress),
+            2 => Ok(Status::Done),
+            _ => Err(invalid("invalid status")),
+        }
+    }
+}
+
+impl Encode for Priority {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let tag: u8 = match self {
+            Priority::Low => 0,
+            Priority::Medium => 1,
+            Priority::High => 2,
+        };
+        tag.encode(buffer);
+    }
+}
+
+impl Decode for Priority {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match tag(input)? {
+            0 => Ok(Priority::Low),
+            1 => Ok(Priority::Medium),
+            2 => Ok(Priority::High),
+            _ => Err(invalid("invalid priority")),
+        }
+    }
+}
+
+impl Encode for StatusChange {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.from.encode(buffer);
+        self.to.encode(buffer);
+        self.at.encode(buffer);
+    }
+}
+
+impl Decode for StatusChange {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(StatusChange {
+            from: Status::decode(input)?,
+            to: Status::decode(input)?,
+            at: SystemTime::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Ticket {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.id.encode(buffer);
+        self.title.encode(buffer);
+        self.description.encode(buffer);
+        self.status.encode(buffer);
+        self.priority.encode(buffer);
+        self.due_date.encode(buffer);
+        self.assignee.encode(buffer);
+        self.created_at.encode(buffer);
+        self.updated_at.encode(buffer);
+        self.status_changes.encode(buffer);
+    }
+}
+
+impl Decode for Ticket {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(Ticket {
+            id: TicketId::decode(input)?,
+            title: TicketTitle::decode(input)?,
+            description: TicketDescription::decode(input)?,
+            status: Status::decode(input)?,
+            priority: Priority::decode(input)?,
+            due_date: Option::decode(input)?,
+            assignee: Option::decode(input)?,
+            created_at: SystemTime::decode(input)?,
+            updated_at: SystemTime::decode(input)?,
+            status_changes: Vec::decode(input)?,
+        })
+    }
+}
+
+impl Encode for TicketDraft {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.title.encode(buffer);
+        self.description.encode(buffer);
+    }
+}
+
+impl Decode for TicketDraft {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(TicketDraft {
+            title: TicketTitle::decode(input)?,
+            description: TicketDescription::decode(input)?,
+        })
+    }
+}
+
+impl Encode for TicketPatch {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.id.encode(buffer);
+        self.title.encode(buffer);
+        self.description.encode(buffer);
+        self.status.encode(buffer);
+        self.priority.encode(buffer);
+        self.due_date.encode(buffer);
+    }
+}
+
+impl Decode for TicketPatch {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(TicketPatch {
+            id: TicketId::decode(input)?,
+            title: Option::decode(input)?,
+            description: Option::decode(input)?,
+            status: Option::decode(input)?,
+            priority: Option::decode(input)?,
+            due_date: Option::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Lease {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.id.encode(buffer);
+        self.holder.encode(buffer);
+        self.until.encode(buffer);
+        self.token.encode(buffer);
+    }
+}
+
+impl Decode for Lease {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(Lease {
+            id: TicketId::decode(input)?,
+            holder: String::decode(input)?,
+            until: SystemTime::decode(input)?,
+            token: u64::decode(input)?,
+        })
+    }
+}
+
+impl Encode for EditError {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            EditError::UnknownTicket(id) => {
+                buffer.push(0);
+                id.encode(buffer);
+            }
+            EditError::Leased { id, holder, until } => {
+                buffer.push(1);
+                id.encode(buffer);
+                holder.encode(buffer);
+                until.encode(buffer);
+            }
+            EditError::LeaseLost(id) => {
+                buffer.push(2);
+                id.encode(buffer);
+            }
+        }
+    }
+}
+
+impl Decode for EditError {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match tag(input)? {
+            0 => Ok(EditError::UnknownTicket(TicketId::decode(input)?)),
+            1 => Ok(EditError::Leased {
+                id: TicketId::decode(input)?,
+                holder: String::decode(input)?,
+                until: SystemTime::decode(input)?,
+            }),
+            2 => Ok(EditError::LeaseLost(TicketId::decode(input)?)),
+            _ => Err(invalid("invalid edit error")),
+        }
+    }
+}
+
+impl Encode for ClientError {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            ClientError::Overloaded => buffer.push(0),
+            ClientError::Disconnected => buffer.push(1),
+            ClientError::ShuttingDown => buffer.push(2),
+            ClientError::RequestDropped => buffer.push(3),
+            ClientError::TimedOut(timeout) => {
+                buffer.push(4);
+                timeout.encode(buffer);
+            }
+            ClientError::Edit(error) => {
+                buffer.push(5);
+                error.encode(buffer);
+            }
+        }
+    }
+}
+
+impl Decode for ClientError {
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match tag(input)? {
+            0 => Ok(ClientError::Overloaded),
+            1 => Ok(ClientError::Disconnected),
+            2 => Ok(ClientError::ShuttingDown),
+            3 => Ok(ClientError::RequestDropped),
+            4 => Ok(ClientError::TimedOut(Duration::decode(input)?)),
+            5 => Ok(ClientError::Edit(EditError::decode(input)?)),
+            _ => Err(invalid("invalid client error")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let ticket = Ticket {
+            id: TicketId(7),
+            title: "A title".try_into().unwrap(),
+            description: "A description".try_into().unwrap(),
+            status: Status::InProgress,
+            priority: Priority::High,
+            due_date: Some(SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 5)),
+            assignee: Some("alice".into()),
+            created_at: SystemTime::UNIX_EPOCH,
+            updated_at: SystemTime::UNIX_EPOCH + Duration::from_secs(60),
+            status_changes: vec![StatusChange {
+                from: Status::ToDo,
+                to: Status::InProgress,
+                at: SystemTime::UNIX_EPOCH + Duration::from_secs(60),
+            }],
+        };
+        let value: Result<Vec<Option<Ticket>>, ClientError> = Ok(vec![Some(ticket), None]);
+        assert_eq!(
+            from_bytes::<Result<Vec<Option<Ticket>>, ClientError>>(&to_bytes(&value)).unwrap(),
+            value
+        );
+
+        let error: Result<(), ClientError> = Err(ClientError::Edit(EditError::Leased {
+            id: TicketId(3),
+            holder: "bob".into(),
+            until: SystemTime::UNIX_EPOCH + Duration::from_secs(5),
+        }));
+        assert_eq!(
+            from_bytes::<Result<(), ClientError>>(&to_bytes(&error)).unwrap(),
+            error
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        let bytes = to_bytes(&Some(TicketId(1)));
+        assert!(from_bytes::<Option<TicketId>>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(from_bytes::<Option<TicketId>>(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(from_bytes::<Status>(&[9]).is_err());
+        // An empty title doesn't make it past validation.
+        assert!(from_bytes::<TicketTitle>(&to_bytes(&String::new())).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+mod common;
+use common::draft;
+use std::time::{Duration, SystemTime};
+use ticket_server::analytics::{
+    burndown, flow_times, render_flow_times, render_series, sample_points, weekly_throughput,
+    work_in_progress, Point, WEEK,
+};
+use ticket_server::clock::ManualClock;
+use ticket_server::data::{Status, TicketPatch};
+use ticket_server::store::{TicketId, TicketStore};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn move_to(store: &mut TicketStore, id: TicketId, status: Status) {
+    store
+        .update(TicketPatch {
+            status: Some(status),
+            ..TicketPatch::new(id)
+        })
+        .unwrap();
+}
+
+#[test]
+fn flow_metrics() {
+    let clock = ManualClock::default();
+    let start = SystemTime::UNIX_EPOCH;
+    let mut store = TicketStore::with_clock(clock.clone());
+
+    let a = store.add_ticket(draft());
+    let b = store.add_ticket(draft());
+    let c = store.add_ticket(draft());
+
+    clock.advance(DAY);
+    move_to(&mut store, a, Status::InProgress);
+    move_to(&mut store, b, Status::InProgress);
+    clock.advance(HOUR * 5);
+    move_to(&mut store, a, Status::Done);
+    clock.advance(WEEK);
+    move_to(&mut store, b, Status::Done);
+
+    let tickets = store.snapshot();
+
+    let times = flow_times(&tickets);
+    assert_eq!(times[0].lead_time, Some(DAY + HOUR * 5));
+    assert_eq!(times[0].cycle_time, Some(HOUR * 5));
+    assert_eq!(times[1].lead_time, Some(DAY + HOUR * 5 + WEEK));
+    assert_eq!(times[1].cycle_time, Some(HOUR * 5 + WEEK));
+    assert_eq!(times[2].lead_time, None);
+    assert_eq!(times[2].cycle_time, None);
+
+    let throughput = weekly_throughput(&tickets, start, 3);
+    let values: Vec<usize> = throughput.iter().map(|p| p.value).collect();
+    assert_eq!(values, vec![1, 1, 0]);
+
+    let samples = sample_points(start, DAY, 3);
+    let wip: Vec<usize> = work_in_progress(&tickets, &samples)
+        .iter()
+        .map(|p| p.value)
+        .collect();
+    assert_eq!(wip, vec![0, 2, 1]);
+
+    let remaining: Vec<usize> = burndown(&tickets, &[a, c], &samples)
+        .iter()
+        .map(|p| p.value)
+        .collect();
+    assert_eq!(remaining, vec![2, 2, 1]);
+}
+
+#[test]
+fn renders_plain_text_tables() {
+    let clock = ManualClock::default();
+    let mut store = TicketStore::with_clock(clock.clone());
+    let id = store.add_ticket(draft());
+    store.add_ticket(draft());
+    clock.advance(HOUR);
+    move_to(&mut store, id, Status::InProgress);
+    clock.advance(DAY + HOUR * 2);
+    move_to(&mut store, id, Status::Done);
+
+    let table = render_flow_times(&flow_times(&store.snapshot()));
+    assert_eq!(
+        table,
+        "Ticket      | Lead time | Cycle time\n\
+         ----------- | --------- | ----------\n\
+         TicketId(0) | 1d 3h 0m  | 1d 2h 0m\n\
+         TicketId(1) | -         | -\n"
+    );
+
+    let series = render_series(
+        "Done",
+        &[Point {
+            at: SystemTime::UNIX_EPOCH + DAY * 365,
+            value: 4,
+        }],
+    );
+    assert_eq!(
+        series,
+        "Date       | Done\n\
+         ---------- | ----\n\
+         1971-01-01 | 4\n"
+    );
+}
+
+#[test]
+fn reopened_and_unfinished_tickets() {
+    let clock = ManualClock::default();
+    let mut store = TicketStore::with_clock(clock.clone());
+    let reopened = store.add_ticket(draft());
+    let abandoned = store.add_ticket(draft());
+
+    clock.advance(HOUR);
+    move_to(&mut store, reopened, Status::InProgress);
+    // Straight to done, without ever being worked on.
+    move_to(&mut store, abandoned, Status::Done);
+    clock.advance(HOUR);
+    move_to(&mut store, reopened, Status::Done);
+    clock.advance(DAY);
+    move_to(&mut store, reopened, Status::InProgress);
+
+    let tickets = store.snapshot();
+    let times = flow_times(&tickets);
+    // Back in progress: it doesn't count as done anymore.
+    assert_eq!(times[0].lead_time, None);
+    assert_eq!(times[0].cycle_time, None);
+    assert_eq!(times[1].lead_time, Some(HOUR));
+    assert_eq!(times[1].cycle_time, None);
+
+    clock.advance(HOUR);
+    move_to(&mut store, reopened, Status::Done);
+    let times = flow_times(&store.snapshot());
+    // Measured up to the last completion, from the first time work started.
+    assert_eq!(times[0].lead_time, Some(DAY + HOUR * 3));
+    assert_eq!(times[0].cycle_time, Some(DAY + HOUR * 2));
+
+    // Nothing existed before the epoch.
+    let before = SystemTime::UNIX_EPOCH - HOUR;
+    let wip = work_in_progress(&tickets, &[before]);
+    assert_eq!(
+        wip,
+        vec![Point {
+            at: before,
+            value: 0
+        }]
+    );
+    assert_eq!(
+        burndown(&tickets, &[], &[before, SystemTime::UNIX_EPOCH + DAY])[1].value,
+        0
+    );
+    assert!(weekly_throughput(&tickets, before, 0).is_empty());
+}
@@ -0,0 +1,89 @@
+mod common;
+use common::draft;
+use std::time::{Duration, SystemTime};
+use ticket_server::arena::ArenaStorage;
+use ticket_server::clock::ManualClock;
+use ticket_server::data::{Status, StatusChange, TicketPatch};
+use ticket_server::store::{TicketId, TicketStore};
+use ticket_server::{ClientError, ServerBuilder};
+
+fn finish(id: TicketId) -> TicketPatch {
+    TicketPatch {
+        status: Some(Status::Done),
+        ..TicketPatch::new(id)
+    }
+}
+
+#[test]
+fn same_operations_as_the_map_backed_store() {
+    let clock = ManualClock::default();
+    let mut store = TicketStore::with_clock(clock.clone()).with_storage(ArenaStorage::default());
+    let id = store.add_ticket(draft());
+    assert_eq!(store.get(id).unwrap().lock().unwrap().id, id);
+
+    clock.advance(Duration::from_secs(10));
+    store.update(finish(id)).unwrap();
+    let ticket = store.get(id).unwrap();
+    let ticket = ticket.lock().unwrap();
+    assert_eq!(ticket.status, Status::Done);
+    assert_eq!(
+        ticket.status_changes,
+        vec![StatusChange {
+            from: Status::ToDo,
+            to: Status::Done,
+            at: SystemTime::UNIX_EPOCH + Duration::from_secs(10),
+        }]
+    );
+}
+
+#[test]
+fn ids_of_deleted_tickets_go_stale() {
+    let mut store = TicketStore::new().with_storage(ArenaStorage::default());
+    let kept = store.add_ticket(draft());
+    let deleted = store.add_ticket(draft());
+    store.delete(deleted).unwrap();
+
+    // The new ticket reuses the slot, but not the id.
+    let reused = store.add_ticket(draft());
+    assert_ne!(deleted, reused);
+    assert!(store.get(deleted).is_none());
+    assert!(store.delete(deleted).unwrap().is_none());
+    assert_eq!(store.get(reused).unwrap().lock().unwrap().id, reused);
+    let listed: Vec<_> = store.snapshot().iter().map(|ticket| ticket.id).collect();
+    assert_eq!(listed, vec![kept, reused]);
+}
+
+#[test]
+fn a_supervised_server_can_run_on_an_arena() {
+    let server = ServerBuilder::new(5)
+        .storage::<ArenaStorage>()
+        .supervised(2)
+        .launch();
+    let first = server.insert(draft()).unwrap();
+    let deleted = server.insert(draft()).unwrap();
+    server.delete(deleted).unwrap();
+    let reused = server.insert(draft()).unwrap();
+
+    // The server crashes on a poisoned ticket, then replays its journal on top of its last
+    // checkpoint: the arena is rebuilt slot for slot, so ids still point at the same tickets.
+    let poisoned = server.get(first).unwrap().unwrap();
+    let _ = std::thread::spawn(move || {
+        let _guard = poisoned.lock().unwrap();
+        panic!("Poisoning the lock");
+    })
+    .join();
+    assert_eq!(
+        server.update(finish(first)),
+        Err(ClientError::RequestDropped)
+    );
+    assert!(server.get(deleted).unwrap().is_none());
+    assert_eq!(server.supervisor_status().restarts, 1);
+    let ticket = server.get(reused).unwrap().unwrap();
+    assert_eq!(ticket.lock().unwrap().id, reused);
+    server.update(finish(reused)).unwrap();
+    let next = server.insert(draft()).unwrap();
+    assert!(![first, deleted, reused].contains(&next));
+    let listed: Vec<_> = server.list().unwrap().iter().map(|t| t.id).collect();
+    // Ordered by id: the generation makes `reused` the greatest one.
+    assert_eq!(listed, vec![first, next, reused]);
+}
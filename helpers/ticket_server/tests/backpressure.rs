@@ -0,0 +1,145 @@
+mod common;
+use common::{draft, rename, wait_until};
+use std::sync::mpsc::channel;
+use std::thread::spawn;
+use std::time::Duration;
+use ticket_server::backpressure::{Backpressure, OverloadCount};
+use ticket_server::launch;
+use ticket_server::ClientError;
+
+#[test]
+fn strategies() {
+    let server = launch(1);
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+
+    // Stall the server on a locked ticket, then fill the queue.
+    let guard = ticket.lock().unwrap();
+    let stalled = server.client().with_timeout(Duration::from_millis(20));
+    assert!(stalled.update(rename(id, "A new title")).is_err());
+    wait_until(|| server.metrics().queue_depth == 0);
+    assert!(stalled.insert(draft()).is_err());
+
+    let fail_fast = server.client();
+    assert_eq!(fail_fast.insert(draft()), Err(ClientError::Overloaded));
+
+    let block_for = server
+        .client()
+        .with_backpressure(Backpressure::BlockFor(Duration::from_millis(20)));
+    assert_eq!(block_for.insert(draft()), Err(ClientError::Overloaded));
+
+    let retry = server.client().with_backpressure(Backpressure::Retry {
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+        deadline: Duration::from_millis(20),
+    });
+    assert_eq!(retry.insert(draft()), Err(ClientError::Overloaded));
+
+    // Release the server while a blocking client is waiting for room in the queue.
+    let block = server.client().with_backpressure(Backpressure::Block);
+    let blocked = spawn(move || block.insert(draft()));
+    wait_until(|| server.overload_stats().block.overloaded == 1);
+    drop(guard);
+    assert!(blocked.join().unwrap().is_ok());
+
+    let stats = server.overload_stats();
+    let rejected_once = OverloadCount {
+        overloaded: 1,
+        rejected: 1,
+    };
+    assert_eq!(stats.fail_fast, rejected_once);
+    assert_eq!(stats.block_for, rejected_once);
+    assert_eq!(stats.retry, rejected_once);
+    assert_eq!(
+        stats.block,
+        OverloadCount {
+            overloaded: 1,
+            rejected: 0,
+        }
+    );
+}
+
+#[test]
+fn retries_get_in_once_the_queue_has_room() {
+    let server = launch(1);
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+
+    let guard = ticket.lock().unwrap();
+    let stalled = server.client().with_timeout(Duration::from_millis(20));
+    assert!(stalled.update(rename(id, "A new title")).is_err());
+    wait_until(|| server.metrics().queue_depth == 0);
+    assert!(stalled.insert(draft()).is_err());
+
+    let retry = server.client().with_backpressure(Backpressure::Retry {
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+        deadline: Duration::from_secs(5),
+    });
+    let retrying = spawn(move || retry.insert(draft()));
+    wait_until(|| server.overload_stats().retry.overloaded == 1);
+    drop(guard);
+    assert!(retrying.join().unwrap().is_ok());
+    assert_eq!(
+        server.overload_stats().retry,
+        OverloadCount {
+            overloaded: 1,
+            rejected: 0,
+        }
+    );
+}
+
+#[test]
+fn waiting_clients_notice_when_the_server_dies() {
+    let server = launch(1);
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+
+    // Stall the server on a locked ticket, and poison it once the clients are waiting.
+    let (locked, held) = channel();
+    let (poison, poisoned) = channel::<()>();
+    let holder = spawn(move || {
+        let _guard = ticket.lock().unwrap();
+        locked.send(()).unwrap();
+        let _ = poisoned.recv();
+        panic!("Poisoning the lock");
+    });
+    held.recv().unwrap();
+    let stalled = server.client().with_timeout(Duration::from_millis(20));
+    assert!(stalled.update(rename(id, "A new title")).is_err());
+    wait_until(|| server.metrics().queue_depth == 0);
+    assert!(stalled.insert(draft()).is_err());
+
+    let waiting: Vec<_> = [
+        Backpressure::Block,
+        Backpressure::BlockFor(Duration::from_secs(5)),
+        Backpressure::Retry {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+            deadline: Duration::from_secs(5),
+        },
+    ]
+    .into_iter()
+    .map(|strategy| {
+        let client = server.client().with_backpressure(strategy);
+        spawn(move || client.insert(draft()))
+    })
+    .collect();
+    wait_until(|| {
+        let stats = server.overload_stats();
+        [stats.block, stats.block_for, stats.retry].map(|count| count.overloaded) == [1; 3]
+    });
+
+    // The server panics on the poisoned lock: nobody is left to make room in the queue.
+    drop(poison);
+    assert!(holder.join().is_err());
+    for client in waiting {
+        assert_eq!(client.join().unwrap(), Err(ClientError::Disconnected));
+    }
+    let stats = server.overload_stats();
+    // Disconnections aren't overloads.
+    assert_eq!(
+        [stats.block, stats.block_for, stats.retry].map(|count| count.rejected),
+        [0; 3]
+    );
+}
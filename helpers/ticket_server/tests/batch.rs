@@ -0,0 +1,134 @@
+mod common;
+use common::{draft, rename, title};
+use std::thread::spawn;
+use std::time::Duration;
+use ticket_fields::test_helpers::ticket_title;
+use ticket_server::leases::EditError;
+use ticket_server::{launch, ClientError, ServerBuilder};
+
+#[test]
+fn batches_have_one_result_per_item() {
+    let server = launch(1);
+    let ids = server.insert_many(vec![draft(), draft(), draft()]).unwrap();
+    assert_eq!(ids.len(), 3);
+    let deleted = server.delete(ids[1]).unwrap().unwrap();
+    let missing = deleted.lock().unwrap().id;
+
+    let results = server
+        .patch_many(vec![
+            rename(ids[0], "First"),
+            rename(missing, "Lost"),
+            rename(ids[2], "Third"),
+        ])
+        .unwrap();
+    assert_eq!(
+        results,
+        vec![Ok(()), Err(EditError::UnknownTicket(missing)), Ok(())]
+    );
+
+    let tickets = server.get_many(ids.clone()).unwrap();
+    let titles: Vec<_> = tickets
+        .iter()
+        .map(|ticket| ticket.as_ref().map(|t| t.lock().unwrap().title.clone()))
+        .collect();
+    assert_eq!(
+        titles,
+        vec![Some(title("First")), None, Some(title("Third"))]
+    );
+}
+
+#[test]
+fn partitioned_batches_keep_their_order() {
+    let server = ServerBuilder::new(5).launch_partitioned(3);
+    let mut ids: Vec<_> = (0..3)
+        .flat_map(|_| server.insert_many(vec![draft(), draft()]).unwrap())
+        .collect();
+    ids.reverse();
+
+    let patches = ids
+        .iter()
+        .map(|&id| rename(id, &format!("{id:?}")))
+        .collect();
+    let results = server.patch_many(patches).unwrap();
+    assert!(results.iter().all(Result::is_ok));
+
+    let tickets = server.get_many(ids.clone()).unwrap();
+    for (id, ticket) in ids.iter().zip(tickets) {
+        let ticket = ticket.unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert_eq!(ticket.id, *id);
+        assert_eq!(ticket.title, title(&format!("{id:?}")));
+    }
+}
+
+#[test]
+fn one_failed_item_does_not_fail_the_batch() {
+    let server = launch(5);
+    let ids = server.insert_many(vec![draft(), draft()]).unwrap();
+    let lease = server
+        .checkout(ids[0], "alice".into(), Duration::from_secs(60))
+        .unwrap();
+
+    let results = server
+        .patch_many(vec![rename(ids[0], "Taken"), rename(ids[1], "Free")])
+        .unwrap();
+    assert_eq!(
+        results,
+        vec![
+            Err(EditError::Leased {
+                id: ids[0],
+                holder: "alice".into(),
+                until: lease.until,
+            }),
+            Ok(())
+        ]
+    );
+    let tickets = server.get_many(ids).unwrap();
+    assert_eq!(
+        tickets[0].as_ref().unwrap().lock().unwrap().title,
+        ticket_title()
+    );
+    assert_eq!(
+        tickets[1].as_ref().unwrap().lock().unwrap().title,
+        title("Free")
+    );
+}
+
+#[test]
+fn empty_batches() {
+    let server = launch(1);
+    assert_eq!(server.insert_many(Vec::new()).unwrap(), Vec::new());
+    assert!(server.get_many(Vec::new()).unwrap().is_empty());
+    assert!(server.patch_many(Vec::new()).unwrap().is_empty());
+
+    let server = ServerBuilder::new(1).launch_partitioned(3);
+    assert_eq!(server.insert_many(Vec::new()).unwrap(), Vec::new());
+    assert!(server.get_many(Vec::new()).unwrap().is_empty());
+    assert!(server.patch_many(Vec::new()).unwrap().is_empty());
+    // No worker was bothered with an empty sub-batch.
+    assert_eq!(server.shutdown().unwrap().commands_processed, 1);
+}
+
+#[test]
+fn a_batch_that_crashes_the_server_is_not_half_applied() {
+    let server = ServerBuilder::new(5).supervised(100).launch();
+    let ids = server.insert_many(vec![draft(), draft(), draft()]).unwrap();
+    let poisoned = server.get(ids[1]).unwrap().unwrap();
+    let _ = spawn(move || {
+        let _guard = poisoned.lock().unwrap();
+        panic!("Poisoning the lock");
+    })
+    .join();
+
+    // The first patch went through before the server crashed on the second one,
+    // but the batch as a whole was never acknowledged: it's gone after the restart.
+    let patches = ids.iter().map(|&id| rename(id, "Lost")).collect();
+    assert_eq!(server.patch_many(patches), Err(ClientError::RequestDropped));
+    let titles: Vec<_> = server
+        .get_many(ids)
+        .unwrap()
+        .into_iter()
+        .map(|ticket| ticket.unwrap().lock().unwrap().title.clone())
+        .collect();
+    assert_eq!(titles, vec![ticket_title(); 3]);
+}
@@ -0,0 +1,104 @@
+mod common;
+use common::draft;
+use std::time::Duration;
+use ticket_fields::test_helpers::ticket_title;
+use ticket_server::board::{Board, BoardError};
+use ticket_server::data::{Status, TicketPatch};
+use ticket_server::leases::EditError;
+use ticket_server::store::TicketStore;
+
+#[test]
+fn wip_limits_are_enforced_unless_forced() {
+    let mut board = Board::new(TicketStore::new()).with_wip_limit(Status::InProgress, 1);
+    let a = board.add_ticket(draft()).unwrap();
+    let b = board.add_ticket(draft()).unwrap();
+
+    board.move_ticket(a, Status::InProgress).unwrap();
+    assert_eq!(
+        board.move_ticket(b, Status::InProgress),
+        Err(BoardError::WipLimitReached {
+            status: Status::InProgress,
+            limit: 1
+        })
+    );
+    assert_eq!(board.column(Status::ToDo), &[b]);
+
+    // Patching the status goes through the same check.
+    let patch = TicketPatch {
+        title: Some("Rejected".try_into().unwrap()),
+        status: Some(Status::InProgress),
+        ..TicketPatch::new(b)
+    };
+    assert!(board.update(patch).is_err());
+    let ticket = board.store().get(b).unwrap();
+    assert_eq!(ticket.lock().unwrap().title, ticket_title());
+
+    board.force_move_ticket(b, Status::InProgress).unwrap();
+    assert_eq!(board.column(Status::InProgress), &[a, b]);
+    assert_eq!(
+        board.store().get(b).unwrap().lock().unwrap().status,
+        Status::InProgress
+    );
+}
+
+#[test]
+fn ordering_survives_unrelated_patches() {
+    let mut board = Board::new(TicketStore::new());
+    let a = board.add_ticket(draft()).unwrap();
+    let b = board.add_ticket(draft()).unwrap();
+    let c = board.add_ticket(draft()).unwrap();
+
+    board.reorder(c, 0).unwrap();
+    assert_eq!(board.column(Status::ToDo), &[c, a, b]);
+
+    board
+        .update(TicketPatch {
+            title: Some("A new title".try_into().unwrap()),
+            status: Some(Status::ToDo),
+            ..TicketPatch::new(a)
+        })
+        .unwrap();
+    assert_eq!(board.column(Status::ToDo), &[c, a, b]);
+
+    board.move_ticket(a, Status::Done).unwrap();
+    assert_eq!(board.column(Status::ToDo), &[c, b]);
+    assert_eq!(board.column(Status::Done), &[a]);
+}
+
+#[test]
+fn failed_moves_leave_the_board_untouched() {
+    let mut store = TicketStore::new();
+    let leased = store.add_ticket(draft());
+    store
+        .checkout(leased, "alice".into(), Duration::from_secs(600))
+        .unwrap();
+    let mut board = Board::new(store).with_wip_limit(Status::ToDo, 2);
+    let free = board.add_ticket(draft()).unwrap();
+    assert_eq!(
+        board.add_ticket(draft()),
+        Err(BoardError::WipLimitReached {
+            status: Status::ToDo,
+            limit: 2
+        })
+    );
+
+    // Someone else is editing the ticket: the store turns the move down.
+    assert!(matches!(
+        board.move_ticket(leased, Status::Done),
+        Err(BoardError::Edit(EditError::Leased { holder, .. })) if holder == "alice"
+    ));
+    assert_eq!(board.column(Status::ToDo), &[leased, free]);
+    assert!(board.column(Status::Done).is_empty());
+
+    // An id from another store, that this board never handed out.
+    let mut other = TicketStore::new();
+    let missing = (0..3).map(|_| other.add_ticket(draft())).last().unwrap();
+    assert_eq!(
+        board.move_ticket(missing, Status::Done),
+        Err(BoardError::UnknownTicket(missing))
+    );
+    assert_eq!(
+        board.reorder(missing, 0),
+        Err(BoardError::UnknownTicket(missing))
+    );
+}
@@ -0,0 +1,55 @@
+mod common;
+use common::{draft, rename};
+use std::thread::spawn;
+use std::time::Duration;
+use ticket_server::backpressure::Backpressure;
+use ticket_server::{launch, ClientError};
+
+#[test]
+fn timeouts_and_overload_are_told_apart() {
+    let timeout = Duration::from_millis(50);
+    let client = launch(1).client().with_timeout(timeout);
+    let id = client.insert(draft()).unwrap();
+
+    // Keep the ticket locked, so that the server gets stuck applying the patch.
+    let ticket = client.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+    assert_eq!(
+        client.update(rename(id, "A new title")),
+        Err(ClientError::TimedOut(timeout))
+    );
+    // The server is busy: this one sits in the queue, filling it up...
+    assert_eq!(client.insert(draft()), Err(ClientError::TimedOut(timeout)));
+    // ...so there's no room for this one.
+    assert_eq!(client.insert(draft()), Err(ClientError::Overloaded));
+
+    drop(guard);
+    let block = client.with_backpressure(Backpressure::Block);
+    assert!(block.insert(draft()).is_ok());
+}
+
+#[test]
+fn a_dead_server_does_not_take_clients_down() {
+    let client = launch(5);
+    let id = client.insert(draft()).unwrap();
+
+    // Poison the ticket lock: the server will panic when it tries to update it.
+    let ticket = client.get(id).unwrap().unwrap();
+    let _ = spawn(move || {
+        let _guard = ticket.lock().unwrap();
+        panic!("Poisoning the lock");
+    })
+    .join();
+
+    assert_eq!(
+        client.update(rename(id, "A new title")),
+        Err(ClientError::RequestDropped)
+    );
+    // The server thread may still be unwinding: a command sent meanwhile
+    // is dropped along with the mailbox. Either way, the next one can't get in.
+    assert!(matches!(
+        client.insert(draft()),
+        Err(ClientError::Disconnected | ClientError::RequestDropped)
+    ));
+    assert_eq!(client.insert(draft()), Err(ClientError::Disconnected));
+}
@@ -0,0 +1,32 @@
+// Helpers shared by the integration tests: each of them only uses some.
+#![allow(dead_code)]
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::TicketTitle;
+use ticket_server::data::{TicketDraft, TicketPatch};
+use ticket_server::store::TicketId;
+
+pub fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+pub fn title(title: &str) -> TicketTitle {
+    title.try_into().unwrap()
+}
+
+pub fn rename(id: TicketId, title: &str) -> TicketPatch {
+    TicketPatch {
+        title: Some(self::title(title)),
+        ..TicketPatch::new(id)
+    }
+}
+
+// Waits for the server to get to a state the test can only see from the outside,
+// e.g. a command leaving the queue.
+pub fn wait_until(mut condition: impl FnMut() -> bool) {
+    while !condition() {
+        std::thread::yield_now();
+    }
+}
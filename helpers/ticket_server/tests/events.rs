@@ -0,0 +1,108 @@
+mod common;
+use common::draft;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_server::clock::ManualClock;
+use ticket_server::data::{Status, StatusChange, TicketPatch};
+use ticket_server::events::{Field, TicketEvent};
+use ticket_server::launch_with_clock;
+use ticket_server::store::TicketStore;
+
+#[test]
+fn callbacks_see_every_committed_change() {
+    let mut store = TicketStore::new();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    store.on_event(move |event| sink.lock().unwrap().push(event.clone()));
+
+    let id = store.add_ticket(draft());
+    store
+        .update(TicketPatch {
+            title: Some("A new title".try_into().unwrap()),
+            description: Some(ticket_description()),
+            ..TicketPatch::new(id)
+        })
+        .unwrap();
+    store.delete(id).unwrap();
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            TicketEvent::Created { id },
+            TicketEvent::FieldChanged {
+                id,
+                field: Field::Title
+            },
+            TicketEvent::Deleted { id },
+        ]
+    );
+}
+
+#[test]
+fn subscribers_receive_events_from_the_server() {
+    let client = launch_with_clock(5, ManualClock::default());
+    let events = client.subscribe().unwrap();
+
+    let id = client.insert(draft()).unwrap();
+    client.claim_next("alice".into()).unwrap();
+    client.delete(id).unwrap();
+
+    let received: Vec<_> = events.try_iter().collect();
+    assert_eq!(
+        received,
+        vec![
+            TicketEvent::Created { id },
+            TicketEvent::FieldChanged {
+                id,
+                field: Field::Assignee
+            },
+            TicketEvent::StatusTransitioned {
+                id,
+                change: StatusChange {
+                    from: Status::ToDo,
+                    to: Status::InProgress,
+                    at: SystemTime::UNIX_EPOCH,
+                }
+            },
+            TicketEvent::Deleted { id },
+        ]
+    );
+    assert!(client.get(id).unwrap().is_none());
+
+    // Dropping the receiver unsubscribes, without disrupting the server.
+    drop(events);
+    client.insert(draft()).unwrap();
+}
+
+#[test]
+fn rejected_writes_emit_nothing_and_streams_end_with_the_server() {
+    let server = launch_with_clock(5, ManualClock::default());
+    let id = server.insert(draft()).unwrap();
+    let events = server.subscribe().unwrap();
+
+    let lease = server
+        .checkout(id, "alice".into(), std::time::Duration::from_secs(60))
+        .unwrap();
+    let patch = TicketPatch {
+        title: Some("Rejected".try_into().unwrap()),
+        ..TicketPatch::new(id)
+    };
+    assert!(server.update(patch.clone()).is_err());
+    // Leases come and go without touching the ticket.
+    server.abandon(lease).unwrap();
+    // Patches that don't change anything aren't reported either.
+    server
+        .update(TicketPatch {
+            title: Some(ticket_title()),
+            ..patch
+        })
+        .unwrap();
+    assert_eq!(events.try_recv(), Err(std::sync::mpsc::TryRecvError::Empty));
+
+    let client = server.client();
+    server.shutdown().unwrap();
+    // The store is gone, and so is every subscription to it.
+    assert!(events.recv().is_err());
+    assert!(client.subscribe().is_err());
+}
@@ -0,0 +1,135 @@
+mod common;
+use common::{draft, rename, title};
+use std::time::{Duration, SystemTime};
+use ticket_fields::test_helpers::ticket_title;
+use ticket_server::clock::ManualClock;
+use ticket_server::leases::EditError;
+use ticket_server::{launch_with_clock, ClientError};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[test]
+fn other_writers_are_turned_away_while_a_lease_is_held() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    let server = launch_with_clock(5, clock.clone());
+    let id = server.insert(draft()).unwrap();
+
+    let lease = server.checkout(id, "alice".into(), MINUTE).unwrap();
+    assert_eq!(lease.until, SystemTime::UNIX_EPOCH + MINUTE);
+    let leased = ClientError::Edit(EditError::Leased {
+        id,
+        holder: "alice".into(),
+        until: lease.until,
+    });
+    assert_eq!(server.update(rename(id, "Bob's")), Err(leased.clone()));
+    assert_eq!(server.checkout(id, "bob".into(), MINUTE), Err(leased));
+
+    clock.advance(MINUTE / 2);
+    let lease = server.renew(&lease, MINUTE).unwrap();
+    assert_eq!(lease.until, SystemTime::UNIX_EPOCH + MINUTE * 3 / 2);
+    clock.advance(MINUTE / 2);
+    server.commit(lease, rename(id, "Alice's")).unwrap();
+
+    let ticket = server.get(id).unwrap().unwrap();
+    assert_eq!(ticket.lock().unwrap().title, title("Alice's"));
+    // The lease is gone: anybody can write again.
+    server.update(rename(id, "Bob's")).unwrap();
+}
+
+#[test]
+fn expired_leases_are_revoked() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    let server = launch_with_clock(5, clock.clone());
+    let id = server.insert(draft()).unwrap();
+
+    let stale = server.checkout(id, "alice".into(), MINUTE).unwrap();
+    clock.advance(MINUTE);
+    let lease = server.checkout(id, "bob".into(), MINUTE).unwrap();
+
+    assert_eq!(
+        server.commit(stale.clone(), rename(id, "Alice's")),
+        Err(ClientError::Edit(EditError::Leased {
+            id,
+            holder: "bob".into(),
+            until: lease.until,
+        }))
+    );
+    server.abandon(lease).unwrap();
+    assert_eq!(
+        server.renew(&stale, MINUTE),
+        Err(ClientError::Edit(EditError::LeaseLost(id)))
+    );
+    let ticket = server.get(id).unwrap().unwrap();
+    assert_eq!(ticket.lock().unwrap().title, ticket_title());
+}
+
+#[test]
+fn claims_pass_over_leased_tickets() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    let server = launch_with_clock(5, clock.clone());
+    let id = server.insert(draft()).unwrap();
+    let next = server.insert(draft()).unwrap();
+    let lease = server.checkout(id, "alice".into(), MINUTE).unwrap();
+
+    // Not even the lease holder gets it: claims don't take a lease.
+    assert_eq!(server.peek_next().unwrap(), Some(next));
+    assert_eq!(server.claim_next("alice".into()).unwrap(), Some(next));
+    assert_eq!(server.claim_next("alice".into()).unwrap(), None);
+    // The leased ticket keeps its place in the queue until the lease is over.
+    clock.advance(MINUTE);
+    assert_eq!(server.claim_next("bob".into()).unwrap(), Some(id));
+    let ticket = server.get(id).unwrap().unwrap();
+    assert_eq!(ticket.lock().unwrap().assignee.as_deref(), Some("bob"));
+    assert_eq!(
+        server.abandon(lease),
+        Err(ClientError::Edit(EditError::LeaseLost(id)))
+    );
+}
+
+#[test]
+fn leases_hold_off_deletions() {
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+    let server = launch_with_clock(5, clock.clone());
+    let id = server.insert(draft()).unwrap();
+    let lease = server.checkout(id, "alice".into(), MINUTE).unwrap();
+
+    assert_eq!(
+        server.delete(id).map(|t| t.is_some()),
+        Err(ClientError::Edit(EditError::Leased {
+            id,
+            holder: "alice".into(),
+            until: lease.until,
+        }))
+    );
+    clock.advance(MINUTE);
+    assert!(server.delete(id).unwrap().is_some());
+}
+
+#[test]
+fn unknown_tickets_are_reported() {
+    let server = launch_with_clock(5, ManualClock::default());
+    let id = server.insert(draft()).unwrap();
+    server.delete(id).unwrap();
+
+    let unknown = ClientError::Edit(EditError::UnknownTicket(id));
+    assert_eq!(server.update(rename(id, "Gone")), Err(unknown.clone()));
+    assert_eq!(server.checkout(id, "alice".into(), MINUTE), Err(unknown));
+    // Deleting twice isn't an error, though: the ticket is gone either way.
+    assert!(server.delete(id).unwrap().is_none());
+}
+
+#[test]
+fn handles_are_not_bound_by_leases() {
+    let server = launch_with_clock(5, ManualClock::default());
+    let id = server.insert(draft()).unwrap();
+    let lease = server.checkout(id, "alice".into(), MINUTE).unwrap();
+
+    // Leases only guard writes that go through the server.
+    let ticket = server.get(id).unwrap().unwrap();
+    ticket.lock().unwrap().title = title("Bob's");
+    assert!(server.update(rename(id, "Carol's")).is_err());
+    assert_eq!(ticket.lock().unwrap().title, title("Bob's"));
+    // The lease holder's write wins, whatever was done through handles in the meantime.
+    server.commit(lease, rename(id, "Alice's")).unwrap();
+    assert_eq!(ticket.lock().unwrap().title, title("Alice's"));
+}
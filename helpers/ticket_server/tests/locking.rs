@@ -0,0 +1,117 @@
+mod common;
+use common::draft;
+use std::thread::spawn;
+use ticket_server::data::Priority;
+use ticket_server::leases::EditError;
+use ticket_server::locking::lock_ticket;
+use ticket_server::{launch, ClientError};
+
+#[test]
+fn swapping_in_opposite_orders_does_not_deadlock() {
+    let server = launch(10);
+    let a = server.insert(draft()).unwrap();
+    let b = server.insert(draft()).unwrap();
+    server.get(a).unwrap().unwrap().lock().unwrap().priority = Priority::High;
+
+    // Each thread names the tickets in a different order: the set locks them by id regardless.
+    let swappers: Vec<_> = [(a, b), (b, a)]
+        .into_iter()
+        .map(|(first, second)| {
+            let tickets = server.lock_set(vec![first, second]).unwrap();
+            spawn(move || {
+                for _ in 0..1000 {
+                    let mut guards = tickets.lock();
+                    let (first, second) = guards.pair_mut(first, second).unwrap();
+                    std::mem::swap(&mut first.priority, &mut second.priority);
+                }
+            })
+        })
+        .collect();
+    for swapper in swappers {
+        swapper.join().unwrap();
+    }
+
+    let guards = server.lock_set(vec![a, b]).unwrap();
+    let guards = guards.lock();
+    assert_eq!(guards.get(a).unwrap().priority, Priority::High);
+    assert_eq!(guards.get(b).unwrap().priority, Priority::Medium);
+}
+
+#[test]
+fn lock_order_inversions_are_reported() {
+    let server = launch(10);
+    let a = server.insert(draft()).unwrap();
+    let b = server.insert(draft()).unwrap();
+    let (first, second) = (
+        server.get(a).unwrap().unwrap(),
+        server.get(b).unwrap().unwrap(),
+    );
+
+    let inversion = spawn(move || {
+        let _second = lock_ticket(&second);
+        let _first = lock_ticket(&first);
+    })
+    .join()
+    .unwrap_err();
+    let message = inversion.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        message,
+        &format!("Lock-order inversion: acquiring ticket {a:?} while holding ticket {b:?}")
+    );
+}
+
+#[test]
+fn sets_need_every_ticket_to_exist() {
+    let server = launch(10);
+    let a = server.insert(draft()).unwrap();
+    let b = server.insert(draft()).unwrap();
+    server.delete(b).unwrap();
+    assert_eq!(
+        server.lock_set(vec![a, b]).map(|set| set.ids().count()),
+        Err(ClientError::Edit(EditError::UnknownTicket(b)))
+    );
+
+    // Duplicates are locked once, and there's no pair to be made of a single ticket.
+    let set = server.lock_set(vec![a, a]).unwrap();
+    assert_eq!(set.ids().collect::<Vec<_>>(), vec![a]);
+    let mut guards = set.lock();
+    assert!(guards.pair_mut(a, a).is_none());
+    assert!(guards.pair_mut(a, b).is_none());
+    assert!(guards.get(b).is_none());
+}
+
+#[test]
+fn locking_a_ticket_twice_is_reported_rather_than_deadlocking() {
+    let server = launch(10);
+    let a = server.insert(draft()).unwrap();
+    let ticket = server.get(a).unwrap().unwrap();
+
+    let relock = spawn(move || {
+        let _guard = lock_ticket(&ticket);
+        let _again = lock_ticket(&ticket);
+    })
+    .join()
+    .unwrap_err();
+    assert_eq!(
+        relock.downcast_ref::<String>().unwrap(),
+        &format!("Lock-order inversion: acquiring ticket {a:?} while holding ticket {a:?}")
+    );
+}
+
+#[test]
+fn released_locks_are_forgotten() {
+    let server = launch(10);
+    let a = server.insert(draft()).unwrap();
+    let b = server.insert(draft()).unwrap();
+    let (first, second) = (
+        server.get(a).unwrap().unwrap(),
+        server.get(b).unwrap().unwrap(),
+    );
+
+    // One at a time, any order goes.
+    drop(lock_ticket(&second));
+    drop(lock_ticket(&first));
+    let set = server.lock_set(vec![a, b]).unwrap();
+    drop(set.lock());
+    let _second = lock_ticket(&second);
+}
@@ -0,0 +1,107 @@
+// Model tests: run them with
+// `RUSTFLAGS="--cfg loom" cargo test -p ticket_server --test loom --release`.
+#![cfg(loom)]
+mod common;
+use common::{draft, title};
+use loom::thread;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::TicketDescription;
+use ticket_server::data::{Status, Ticket, TicketPatch};
+use ticket_server::store::TicketStore;
+
+fn description(description: &str) -> TicketDescription {
+    description.try_into().unwrap()
+}
+
+// The store itself is never shared: the server thread owns it, and applies inserts and patches
+// one at a time. What is shared are the tickets, through the handles the store hands out.
+
+#[test]
+fn patches_from_the_server_and_edits_through_handles_are_not_lost() {
+    loom::model(|| {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket(draft());
+        let handle = store.get(id).unwrap();
+
+        let server = thread::spawn(move || {
+            let patch = TicketPatch {
+                title: Some(title("Renamed")),
+                ..TicketPatch::new(id)
+            };
+            store.update(patch).unwrap();
+            store
+        });
+        let starter = {
+            let handle = handle.clone();
+            thread::spawn(move || handle.lock().unwrap().status = Status::InProgress)
+        };
+        let store = server.join().unwrap();
+        starter.join().unwrap();
+
+        let ticket = store.get(id).unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert_eq!(ticket.title, title("Renamed"));
+        assert_eq!(ticket.status, Status::InProgress);
+    });
+}
+
+#[test]
+fn handle_holders_never_see_half_applied_patches() {
+    loom::model(|| {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket(draft());
+        let handle = store.get(id).unwrap();
+
+        let server = thread::spawn(move || {
+            let patch = TicketPatch {
+                title: Some(title("Renamed")),
+                description: Some(description("Described")),
+                ..TicketPatch::new(id)
+            };
+            store.update(patch).unwrap();
+        });
+        let seen = handle.lock().unwrap().clone();
+        server.join().unwrap();
+
+        let before = (ticket_title(), ticket_description());
+        let after = (title("Renamed"), description("Described"));
+        let seen = (seen.title, seen.description);
+        assert!(seen == before || seen == after);
+    });
+}
+
+#[test]
+fn handle_holders_never_lose_each_others_edits() {
+    loom::model(|| {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket(draft());
+        let handle = store.get(id).unwrap();
+
+        // The server keeps inserting while clients edit the ticket they hold.
+        let server = thread::spawn(move || {
+            let other = store.add_ticket(draft());
+            (store, other)
+        });
+        let editors: Vec<_> = [
+            Box::new(|ticket: &mut Ticket| ticket.title = title("Renamed"))
+                as Box<dyn FnOnce(&mut Ticket) + Send>,
+            Box::new(|ticket: &mut Ticket| ticket.status = Status::Done),
+        ]
+        .into_iter()
+        .map(|edit| {
+            let handle = handle.clone();
+            thread::spawn(move || edit(&mut handle.lock().unwrap()))
+        })
+        .collect();
+        let (store, other) = server.join().unwrap();
+        for editor in editors {
+            editor.join().unwrap();
+        }
+
+        assert_ne!(other, id);
+        let ticket = store.get(id).unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert_eq!(ticket.title, title("Renamed"));
+        assert_eq!(ticket.status, Status::Done);
+    });
+}
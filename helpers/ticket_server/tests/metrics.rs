@@ -0,0 +1,161 @@
+mod common;
+use common::{draft, wait_until};
+use std::thread::spawn;
+use std::time::Duration;
+use ticket_server::data::TicketPatch;
+use ticket_server::metrics::{render_prometheus, CommandKind, ErrorKind};
+use ticket_server::{launch, ClientError, ServerBuilder};
+
+#[test]
+fn commands_are_counted_and_timed() {
+    let server = launch(5);
+    let id = server.insert(draft()).unwrap();
+    server.insert_many(vec![draft(), draft()]).unwrap();
+    server.get(id).unwrap();
+    server.get(id).unwrap();
+
+    // The server records how long a command took after responding to it:
+    // once it has shut down, it's done recording.
+    let client = server.client();
+    server.shutdown().unwrap();
+    let metrics = client.metrics();
+    assert_eq!(metrics.commands[&CommandKind::Insert], 1);
+    assert_eq!(metrics.commands[&CommandKind::InsertMany], 1);
+    assert_eq!(metrics.commands[&CommandKind::Get], 2);
+    assert_eq!(metrics.commands[&CommandKind::Delete], 0);
+    assert_eq!(metrics.processing.count(), 4);
+    assert_eq!(metrics.latency.count(), 4);
+    assert!(metrics.latency.sum >= metrics.processing.sum);
+    assert_eq!(metrics.queue_depth, 0);
+    assert_eq!(metrics.capacity, 5);
+
+    let text = render_prometheus(&metrics);
+    assert!(text.contains("# TYPE ticket_store_commands_total counter\n"));
+    assert!(text.contains("ticket_store_commands_total{command=\"get\"} 2\n"));
+    assert!(text.contains("ticket_store_processing_seconds_bucket{le=\"+Inf\"} 4\n"));
+    assert!(text.contains("ticket_store_request_latency_seconds_count 4\n"));
+    assert!(text.contains("ticket_store_queue_capacity 5\n"));
+}
+
+#[test]
+fn queue_depth_and_overloads_are_visible() {
+    let server = launch(2);
+    let client = server.client().with_timeout(Duration::from_millis(10));
+    let id = client.insert(draft()).unwrap();
+
+    // Keep the ticket locked, so that the server gets stuck applying the patch.
+    let ticket = client.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+    assert!(client.update(TicketPatch::new(id)).is_err());
+    assert!(client.insert(draft()).is_err());
+    assert!(client.insert(draft()).is_err());
+    assert!(client.insert(draft()).is_err());
+
+    let metrics = server.metrics();
+    assert_eq!(metrics.queue_depth, 2);
+    assert_eq!(metrics.overloads.fail_fast.rejected, 1);
+    let text = render_prometheus(&metrics);
+    assert!(text.contains("ticket_store_queue_depth 2\n"));
+    assert!(text.contains("ticket_store_rejected_total{strategy=\"fail_fast\"} 1\n"));
+
+    drop(guard);
+    server.shutdown().unwrap();
+    assert_eq!(client.metrics().queue_depth, 0);
+}
+
+#[test]
+fn failed_requests_are_timed_and_counted_by_error() {
+    let server = launch(5);
+    let client = server.client().with_timeout(Duration::from_millis(10));
+    let id = client.insert(draft()).unwrap();
+
+    let ticket = client.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+    assert_eq!(
+        client.update(TicketPatch::new(id)),
+        Err(ClientError::TimedOut(Duration::from_millis(10)))
+    );
+    drop(guard);
+    server.shutdown().unwrap();
+
+    // The server got to the patch eventually, but the client had given up on it:
+    // the request took as long as the client was willing to wait.
+    let metrics = client.metrics();
+    assert_eq!(metrics.commands[&CommandKind::Update], 1);
+    assert_eq!(metrics.processing.count(), 3);
+    assert_eq!(metrics.latency.count(), 3);
+    assert!(metrics.latency.sum >= Duration::from_millis(10));
+    assert_eq!(metrics.errors[&ErrorKind::TimedOut], 1);
+    assert_eq!(metrics.errors.values().sum::<u64>(), 1);
+    assert_eq!(metrics.queue_depth, 0);
+
+    let text = render_prometheus(&metrics);
+    assert!(text.contains("ticket_store_request_errors_total{error=\"timed_out\"} 1\n"));
+    assert!(text.contains("ticket_store_request_errors_total{error=\"overloaded\"} 0\n"));
+}
+
+#[test]
+fn commands_that_crash_the_server_are_not_counted() {
+    let server = launch(5);
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+    let _ = spawn(move || {
+        let _guard = ticket.lock().unwrap();
+        panic!("Poisoning the lock");
+    })
+    .join();
+    assert_eq!(
+        server.update(TicketPatch::new(id)),
+        Err(ClientError::RequestDropped)
+    );
+    let client = server.client();
+    assert!(server.shutdown().is_err());
+
+    let metrics = client.metrics();
+    assert_eq!(metrics.commands[&CommandKind::Update], 0);
+    assert_eq!(metrics.processing.count(), 2);
+    assert_eq!(metrics.latency.count(), 3);
+    assert_eq!(metrics.errors[&ErrorKind::RequestDropped], 1);
+    // It did leave the queue, though.
+    assert_eq!(metrics.queue_depth, 0);
+    assert_eq!(metrics.overloads.fail_fast.rejected, 0);
+}
+
+#[test]
+fn overloads_are_counted_as_errors() {
+    let server = launch(1);
+    let client = server.client().with_timeout(Duration::from_millis(10));
+    let id = client.insert(draft()).unwrap();
+
+    let ticket = client.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+    assert!(client.update(TicketPatch::new(id)).is_err());
+    wait_until(|| server.metrics().queue_depth == 0);
+    assert!(client.insert(draft()).is_err());
+    assert_eq!(client.insert(draft()), Err(ClientError::Overloaded));
+
+    let metrics = server.metrics();
+    assert_eq!(metrics.errors[&ErrorKind::Overloaded], 1);
+    assert_eq!(metrics.errors[&ErrorKind::TimedOut], 2);
+    assert_eq!(metrics.latency.count(), 5);
+    drop(guard);
+}
+
+#[test]
+fn fan_out_requests_are_timed_by_each_worker() {
+    let server = ServerBuilder::new(5).launch_partitioned(2);
+    let ids = vec![
+        server.insert(draft()).unwrap(),
+        server.insert(draft()).unwrap(),
+    ];
+    server.get_many(ids).unwrap();
+    server.list().unwrap();
+
+    // One insert, one sub-batch and one listing each.
+    let client = server.client();
+    server.shutdown().unwrap();
+    for metrics in client.metrics() {
+        assert_eq!(metrics.processing.count(), 3);
+        assert_eq!(metrics.latency.count(), 3);
+    }
+}
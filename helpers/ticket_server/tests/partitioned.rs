@@ -0,0 +1,161 @@
+mod common;
+use common::draft;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::Duration;
+use ticket_server::data::{Priority, Ticket, TicketPatch};
+use ticket_server::events::TicketEvent;
+use ticket_server::store::TicketId;
+use ticket_server::ServerBuilder;
+
+fn prioritize(id: TicketId, priority: Priority) -> TicketPatch {
+    TicketPatch {
+        priority: Some(priority),
+        ..TicketPatch::new(id)
+    }
+}
+
+#[test]
+fn commands_are_routed_to_the_worker_owning_the_ticket() {
+    let server = ServerBuilder::new(10).launch_partitioned(4);
+    let ids: Vec<_> = (0..8).map(|_| server.insert(draft()).unwrap()).collect();
+
+    for &id in &ids {
+        server
+            .update(TicketPatch {
+                title: Some("Routed".to_string().try_into().unwrap()),
+                ..TicketPatch::new(id)
+            })
+            .unwrap();
+        let ticket = server.get(id).unwrap().unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert_eq!(ticket.id, id);
+        assert_eq!(ticket.title, "Routed".to_string().try_into().unwrap());
+    }
+
+    assert!(server.delete(ids[5]).unwrap().is_some());
+    assert!(server.get(ids[5]).unwrap().is_none());
+
+    // Listing fans out to every worker and merges the results, in id order.
+    let listed: Vec<_> = server.list().unwrap().iter().map(|t| t.id).collect();
+    let mut expected = ids.clone();
+    expected.remove(5);
+    expected.sort();
+    assert_eq!(listed, expected);
+}
+
+#[test]
+fn workers_shut_down_together() {
+    let flushed: Arc<Mutex<Vec<Ticket>>> = Arc::default();
+    let sink = flushed.clone();
+    let server = ServerBuilder::new(100)
+        .persistence(move |tickets: &[Ticket]| {
+            sink.lock().unwrap().extend_from_slice(tickets);
+            Ok(())
+        })
+        .launch_partitioned(3);
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let client = server.client();
+            spawn(move || {
+                for _ in 0..10 {
+                    client.insert(draft()).unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let report = server.shutdown().unwrap();
+    assert_eq!(report.commands_processed, 40);
+    assert!(report.flushed.unwrap().is_ok());
+    let flushed = flushed.lock().unwrap();
+    assert_eq!(flushed.len(), 40);
+    assert!(flushed.windows(2).all(|pair| pair[0].id < pair[1].id));
+}
+
+#[test]
+fn tickets_are_claimed_in_priority_order_across_workers() {
+    let server = ServerBuilder::new(10).launch_partitioned(3);
+    // Round-robin: each worker gets one ticket of each priority.
+    let ids: Vec<_> = (0..6).map(|_| server.insert(draft()).unwrap()).collect();
+    let priorities = [Priority::Low, Priority::Medium, Priority::High];
+    for (id, priority) in ids.iter().zip(priorities.iter().cycle()) {
+        server.update(prioritize(*id, *priority)).unwrap();
+    }
+    assert_eq!(server.peek_next().unwrap(), Some(ids[2]));
+
+    let claimed: Vec<_> =
+        std::iter::from_fn(|| server.claim_next("alice".into()).unwrap()).collect();
+    // Same priority: the oldest ticket first.
+    assert_eq!(
+        claimed,
+        vec![ids[2], ids[5], ids[1], ids[4], ids[0], ids[3]]
+    );
+    for id in claimed {
+        let ticket = server.get(id).unwrap().unwrap();
+        assert_eq!(ticket.lock().unwrap().assignee.as_deref(), Some("alice"));
+    }
+    assert_eq!(server.peek_next().unwrap(), None);
+}
+
+#[test]
+fn claims_across_workers_pass_over_leased_tickets() {
+    let server = ServerBuilder::new(10).launch_partitioned(2);
+    let ids: Vec<_> = (0..2).map(|_| server.insert(draft()).unwrap()).collect();
+    server.update(prioritize(ids[0], Priority::High)).unwrap();
+    server
+        .checkout(ids[0], "alice".into(), Duration::from_secs(60))
+        .unwrap();
+
+    assert_eq!(server.peek_next().unwrap(), Some(ids[1]));
+    assert_eq!(server.claim_next("bob".into()).unwrap(), Some(ids[1]));
+    assert_eq!(server.claim_next("bob".into()).unwrap(), None);
+}
+
+#[test]
+fn concurrent_claims_across_workers_never_collide() {
+    let server = ServerBuilder::new(100).launch_partitioned(3);
+    for _ in 0..30 {
+        server.insert(draft()).unwrap();
+    }
+    let workers: Vec<_> = (0..4)
+        .map(|i| {
+            let client = server.client();
+            spawn(move || {
+                std::iter::from_fn(|| client.claim_next(format!("engineer-{i}")).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let claimed: Vec<_> = workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap())
+        .collect();
+    assert_eq!(claimed.len(), 30);
+    assert_eq!(claimed.iter().collect::<BTreeSet<_>>().len(), 30);
+}
+
+#[test]
+fn subscribers_hear_from_every_worker_until_they_all_stop() {
+    let server = ServerBuilder::new(10).launch_partitioned(3);
+    let events = server.subscribe().unwrap();
+    let ids: BTreeSet<_> = (0..3).map(|_| server.insert(draft()).unwrap()).collect();
+    server.delete(*ids.first().unwrap()).unwrap();
+    server.shutdown().unwrap();
+
+    let events: Vec<_> = events.into_iter().collect();
+    let created: BTreeSet<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            TicketEvent::Created { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(created, ids);
+    assert_eq!(events.len(), 4);
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use ticket_fields::test_helpers::ticket_title;
+use ticket_server::data::{Priority, Status, TicketDraft, TicketPatch};
+use ticket_server::query::QueryEngine;
+use ticket_server::store::TicketStore;
+
+fn archive(size: usize) -> TicketStore {
+    let mut store = TicketStore::new();
+    for i in 0..size {
+        let description = if i % 7 == 0 {
+            "Printer is ON FIRE"
+        } else {
+            "Flaky test"
+        };
+        let id = store.add_ticket(TicketDraft {
+            title: ticket_title(),
+            description: description.try_into().unwrap(),
+        });
+        let status = [Status::ToDo, Status::InProgress, Status::Done][i % 3];
+        store
+            .update(TicketPatch {
+                status: Some(status),
+                priority: Some([Priority::Low, Priority::High][i % 2]),
+                ..TicketPatch::new(id)
+            })
+            .unwrap();
+    }
+    store
+}
+
+#[test]
+fn parallel_results_match_a_single_thread() {
+    let tickets = archive(1000).snapshot();
+    let sequential = QueryEngine::new(1);
+    for threads in [2, 3, 8, 2000] {
+        let engine = QueryEngine::new(threads);
+        assert_eq!(
+            engine.count_by_status(&tickets),
+            sequential.count_by_status(&tickets)
+        );
+        assert_eq!(
+            engine.search_descriptions(&tickets, "on fire"),
+            sequential.search_descriptions(&tickets, "on fire")
+        );
+        let urgent = |t: &ticket_server::data::Ticket| t.priority == Priority::High;
+        assert_eq!(
+            engine.filter(&tickets, urgent),
+            sequential.filter(&tickets, urgent)
+        );
+        assert_eq!(engine.count(&tickets, urgent), 500);
+    }
+}
+
+#[test]
+fn group_by() {
+    let tickets = archive(10).snapshot();
+    let engine = QueryEngine::new(4);
+    assert_eq!(
+        engine.count_by_status(&tickets),
+        BTreeMap::from([
+            (Status::ToDo, 4),
+            (Status::InProgress, 3),
+            (Status::Done, 3)
+        ])
+    );
+    assert_eq!(
+        engine
+            .group_by(&tickets, |t| (t.status, t.priority))
+            .get(&(Status::ToDo, Priority::High)),
+        Some(&2)
+    );
+    let found = engine.search_descriptions(&tickets, "FIRE");
+    assert_eq!(found, vec![tickets[0].id, tickets[7].id]);
+    assert!(engine.search_descriptions(&[], "fire").is_empty());
+}
+
+#[test]
+#[should_panic(expected = "at least one thread")]
+fn an_engine_needs_threads() {
+    QueryEngine::new(0);
+}
+
+#[test]
+#[should_panic(expected = "Unreadable ticket")]
+fn panics_in_a_worker_reach_the_caller() {
+    let tickets = archive(100).snapshot();
+    let unreadable = tickets[42].id;
+    QueryEngine::new(4).count(&tickets, |ticket| {
+        assert_ne!(ticket.id, unreadable, "Unreadable ticket");
+        true
+    });
+}
+
+#[test]
+fn no_tickets_no_results() {
+    let engine = QueryEngine::new(4);
+    assert!(engine.count_by_status(&[]).is_empty());
+    assert_eq!(engine.count(&[], |_| true), 0);
+    assert!(engine.filter(&[], |_| true).is_empty());
+
+    // An empty needle is found in every description.
+    let tickets = archive(5).snapshot();
+    assert_eq!(engine.search_descriptions(&tickets, "").len(), 5);
+    assert!(engine.search_descriptions(&tickets, "nowhere").is_empty());
+}
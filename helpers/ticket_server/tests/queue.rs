@@ -0,0 +1,132 @@
+mod common;
+use common::draft;
+use std::collections::BTreeSet;
+use std::thread::spawn;
+use std::time::{Duration, SystemTime};
+use ticket_server::clock::ManualClock;
+use ticket_server::data::{Priority, Status, TicketPatch};
+use ticket_server::launch_with_clock;
+use ticket_server::store::TicketStore;
+
+#[test]
+fn orders_by_priority_then_due_date_then_age() {
+    let clock = ManualClock::default();
+    let client = launch_with_clock(10, clock.clone());
+
+    let oldest = client.insert(draft()).unwrap();
+    clock.advance(Duration::from_secs(1));
+    let no_due_date = client.insert(draft()).unwrap();
+    clock.advance(Duration::from_secs(1));
+    let due_later = client.insert(draft()).unwrap();
+    let due_sooner = client.insert(draft()).unwrap();
+    let urgent = client.insert(draft()).unwrap();
+    assert_eq!(client.peek_next().unwrap(), Some(oldest));
+
+    let now = SystemTime::UNIX_EPOCH;
+    for (id, priority, due_date) in [
+        (oldest, Priority::Low, None),
+        (no_due_date, Priority::Medium, None),
+        (
+            due_later,
+            Priority::Medium,
+            Some(now + Duration::from_secs(200)),
+        ),
+        (
+            due_sooner,
+            Priority::Medium,
+            Some(now + Duration::from_secs(100)),
+        ),
+        (urgent, Priority::High, None),
+    ] {
+        client
+            .update(TicketPatch {
+                priority: Some(priority),
+                due_date,
+                ..TicketPatch::new(id)
+            })
+            .unwrap();
+    }
+
+    // Tickets that are started by other means leave the queue.
+    client
+        .update(TicketPatch {
+            status: Some(Status::InProgress),
+            ..TicketPatch::new(due_sooner)
+        })
+        .unwrap();
+
+    let mut order = Vec::new();
+    while let Some(id) = client.claim_next("alice".into()).unwrap() {
+        let ticket = client.get(id).unwrap().unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert_eq!(ticket.assignee.as_deref(), Some("alice"));
+        assert_eq!(ticket.status, Status::InProgress);
+        order.push(id);
+    }
+    assert_eq!(order, vec![urgent, due_later, no_due_date, oldest]);
+    assert_eq!(client.peek_next().unwrap(), None);
+}
+
+#[test]
+fn concurrent_claims_never_hand_out_the_same_ticket() {
+    let client = launch_with_clock(100, ManualClock::default());
+    for _ in 0..50 {
+        client.insert(draft()).unwrap();
+    }
+
+    let workers: Vec<_> = (0..4)
+        .map(|i| {
+            let client = client.clone();
+            spawn(move || {
+                let mut claimed = Vec::new();
+                while let Some(id) = client.claim_next(format!("engineer-{i}")).unwrap() {
+                    claimed.push(id);
+                }
+                claimed
+            })
+        })
+        .collect();
+
+    let claimed: Vec<_> = workers
+        .into_iter()
+        .flat_map(|w| w.join().unwrap())
+        .collect();
+    let unique: BTreeSet<_> = claimed.iter().collect();
+    assert_eq!(claimed.len(), 50);
+    assert_eq!(unique.len(), 50);
+}
+
+#[test]
+fn tickets_edited_through_handles_stay_in_the_queue() {
+    let client = launch_with_clock(10, ManualClock::default());
+    let first = client.insert(draft()).unwrap();
+    let second = client.insert(draft()).unwrap();
+    assert_eq!(client.peek_next().unwrap(), Some(first));
+
+    // The queue isn't told about these: it finds out when it looks at the tickets.
+    let handle = client.get(second).unwrap().unwrap();
+    handle.lock().unwrap().priority = Priority::High;
+    assert_eq!(client.peek_next().unwrap(), Some(second));
+    handle.lock().unwrap().priority = Priority::Low;
+    assert_eq!(client.peek_next().unwrap(), Some(first));
+
+    let claimed: Vec<_> = std::iter::from_fn(|| client.claim_next("bob".into()).unwrap()).collect();
+    assert_eq!(claimed, vec![first, second]);
+}
+
+#[test]
+fn clones_both_see_edits_made_through_handles() {
+    let mut a = TicketStore::with_clock(ManualClock::default());
+    let first = a.add_ticket(draft());
+    let second = a.add_ticket(draft());
+    let mut b = a.clone();
+
+    a.get(second).unwrap().lock().unwrap().priority = Priority::High;
+    assert_eq!(a.peek_next(), Some(second));
+    assert_eq!(b.peek_next(), Some(second));
+
+    // And the other way around.
+    b.get(second).unwrap().lock().unwrap().priority = Priority::Low;
+    assert_eq!(b.peek_next(), Some(first));
+    assert_eq!(a.peek_next(), Some(first));
+}
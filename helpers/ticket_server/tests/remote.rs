@@ -0,0 +1,114 @@
+#![cfg(unix)]
+mod common;
+use common::draft;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+use ticket_fields::test_helpers::ticket_title;
+use ticket_server::data::{Status, TicketPatch};
+use ticket_server::leases::EditError;
+use ticket_server::remote::{serve, RemoteClient};
+use ticket_server::{launch, ClientError};
+
+fn socket(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("ticket_server-{name}-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn remote_clients_share_the_store() {
+    let path = socket("share");
+    let server = launch(10);
+    let _remote = serve(&path, server.client()).unwrap();
+
+    let cli = RemoteClient::connect(&path).unwrap();
+    let web = RemoteClient::connect(&path).unwrap();
+    let id = cli.insert(draft()).unwrap();
+    web.update(TicketPatch {
+        status: Some(Status::InProgress),
+        ..TicketPatch::new(id)
+    })
+    .unwrap();
+
+    let ticket = cli.get(id).unwrap().unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.title, ticket_title());
+    // Both see the same store as in-process clients do.
+    assert_eq!(
+        server.get(id).unwrap().unwrap().lock().unwrap().clone(),
+        ticket
+    );
+    assert_eq!(web.list().unwrap(), vec![ticket]);
+    assert_eq!(cli.get_many(vec![id]).unwrap().len(), 1);
+
+    let lease = cli
+        .checkout(id, "cli".into(), Duration::from_secs(60))
+        .unwrap();
+    assert!(matches!(
+        web.checkout(id, "web".into(), Duration::from_secs(60)),
+        Err(ClientError::Edit(EditError::Leased { holder, .. })) if holder == "cli"
+    ));
+    cli.abandon(lease).unwrap();
+    assert!(web.delete(id).unwrap().is_some());
+    assert_eq!(cli.get(id).unwrap(), None);
+}
+
+#[test]
+fn clients_notice_when_the_server_goes_away() {
+    let path = socket("gone");
+    let server = launch(10);
+    let remote = serve(&path, server.client()).unwrap();
+    let client = RemoteClient::connect(&path).unwrap();
+    client.insert(draft()).unwrap();
+
+    server.shutdown().unwrap();
+    // The connection thread forwards to a server that's shutting down...
+    assert_eq!(client.insert(draft()), Err(ClientError::ShuttingDown));
+    // ...until the connection is closed, and nobody is listening to re-establish it.
+    drop(remote);
+    assert_eq!(client.insert(draft()), Err(ClientError::Disconnected));
+    assert!(RemoteClient::connect(&path).is_err());
+}
+
+#[test]
+fn dropping_the_remote_server_closes_idle_connections() {
+    let path = socket("idle");
+    let server = launch(10);
+    let remote = serve(&path, server.client()).unwrap();
+    let idle = RemoteClient::connect(&path).unwrap();
+    let mut raw = UnixStream::connect(&path).unwrap();
+    idle.insert(draft()).unwrap();
+
+    // Doesn't wait for the peers to hang up.
+    drop(remote);
+    assert_eq!(idle.insert(draft()), Err(ClientError::Disconnected));
+    raw.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    assert_eq!(raw.read(&mut [0; 1]).unwrap(), 0);
+    // The in-process server is still there.
+    assert!(server.insert(draft()).is_ok());
+}
+
+#[test]
+fn malformed_requests_only_cost_their_own_connection() {
+    let path = socket("malformed");
+    let server = launch(10);
+    let _remote = serve(&path, server.client()).unwrap();
+    let client = RemoteClient::connect(&path).unwrap();
+
+    let mut raw = UnixStream::connect(&path).unwrap();
+    raw.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    // A one-byte frame, with a tag that isn't a request.
+    raw.write_all(&[0, 0, 0, 1, 255]).unwrap();
+    assert_eq!(raw.read(&mut [0; 1]).unwrap(), 0);
+
+    let mut raw = UnixStream::connect(&path).unwrap();
+    raw.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    // A frame claiming to be larger than anything we'd accept.
+    raw.write_all(&u32::MAX.to_be_bytes()).unwrap();
+    assert_eq!(raw.read(&mut [0; 1]).unwrap(), 0);
+
+    assert!(client.insert(draft()).is_ok());
+}
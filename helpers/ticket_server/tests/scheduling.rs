@@ -0,0 +1,118 @@
+mod common;
+use common::{draft, wait_until};
+use std::thread::{scope, sleep};
+use std::time::Duration;
+use ticket_server::data::TicketPatch;
+use ticket_server::scheduling::SchedulingPolicy;
+use ticket_server::{ClientError, ServerBuilder};
+
+#[derive(Clone, Copy)]
+enum Op {
+    Insert,
+    List,
+}
+
+// Queues `ops`, in order, behind a command that keeps the server busy, then lets the server go.
+// Returns how many tickets each `List` saw.
+fn seen_by_lists(builder: ServerBuilder, ops: &[Op]) -> Vec<usize> {
+    let server = builder.launch();
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+
+    let seen = scope(|scope| {
+        // The server blocks on the ticket lock while processing this update.
+        scope.spawn(|| server.update(TicketPatch::new(id)));
+        sleep(Duration::from_millis(50));
+
+        let queued: Vec<_> = ops
+            .iter()
+            .enumerate()
+            .map(|(i, op)| {
+                let client = server.client();
+                let op = *op;
+                let queued = scope.spawn(move || match op {
+                    Op::Insert => client.insert(draft()).map(|_| None),
+                    Op::List => client.list().map(|tickets| Some(tickets.len())),
+                });
+                while server.metrics().queue_depth < i + 1 {
+                    sleep(Duration::from_millis(1));
+                }
+                queued
+            })
+            .collect();
+        drop(guard);
+        queued
+            .into_iter()
+            .filter_map(|op| op.join().unwrap().unwrap())
+            .collect()
+    });
+    server.shutdown().unwrap();
+    seen
+}
+
+#[test]
+fn reads_jump_ahead_of_writes_with_read_priority() {
+    let ops = [Op::Insert, Op::Insert, Op::List];
+    let fifo = ServerBuilder::new(16);
+    assert_eq!(seen_by_lists(fifo, &ops), vec![3]);
+    let read_priority = ServerBuilder::new(16).scheduling(SchedulingPolicy::ReadPriority);
+    assert_eq!(seen_by_lists(read_priority, &ops), vec![1]);
+
+    let weighted = ServerBuilder::new(16).scheduling(SchedulingPolicy::weighted_fair(2, 1));
+    let ops = [Op::Insert, Op::Insert, Op::List, Op::List, Op::List];
+    // Two reads, one write, then the last read goes before the last write.
+    assert_eq!(seen_by_lists(weighted, &ops), vec![1, 1, 2]);
+}
+
+#[test]
+fn writes_are_not_starved_by_a_stream_of_reads() {
+    let builder = ServerBuilder::new(16)
+        .scheduling(SchedulingPolicy::ReadPriority)
+        .starvation_limit(2);
+    let ops = [Op::Insert, Op::List, Op::List, Op::List, Op::List];
+    // Two reads jump ahead of the insert, then it's let through.
+    assert_eq!(seen_by_lists(builder, &ops), vec![1, 1, 2, 2]);
+}
+
+#[test]
+fn reads_do_not_queue_up_behind_writes() {
+    let ops = [Op::Insert, Op::Insert, Op::Insert, Op::List];
+    let fifo = ServerBuilder::new(16).lookahead(2);
+    assert_eq!(seen_by_lists(fifo, &ops), vec![4]);
+    // The list arrived behind more writes than the policy gets to look at,
+    // but reads have a lane of their own.
+    let read_priority = ServerBuilder::new(16)
+        .scheduling(SchedulingPolicy::ReadPriority)
+        .lookahead(2);
+    assert_eq!(seen_by_lists(read_priority, &ops), vec![1]);
+}
+
+#[test]
+#[should_panic(expected = "at least one")]
+fn zero_weights_are_rejected() {
+    SchedulingPolicy::weighted_fair(1, 0);
+}
+
+#[test]
+fn a_full_write_lane_does_not_turn_reads_away() {
+    let server = ServerBuilder::new(1)
+        .scheduling(SchedulingPolicy::ReadPriority)
+        .launch();
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+
+    let timeout = Duration::from_millis(20);
+    let stalled = server.client().with_timeout(timeout);
+    assert!(stalled.update(TicketPatch::new(id)).is_err());
+    wait_until(|| server.metrics().queue_depth == 0);
+    assert!(stalled.insert(draft()).is_err());
+    assert_eq!(stalled.insert(draft()), Err(ClientError::Overloaded));
+    // The server is still stuck, but there's room for the read.
+    assert_eq!(stalled.list().err(), Some(ClientError::TimedOut(timeout)));
+    assert_eq!(server.metrics().capacity, 2);
+
+    drop(guard);
+    server.shutdown().unwrap();
+}
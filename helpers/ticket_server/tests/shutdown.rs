@@ -0,0 +1,107 @@
+mod common;
+use common::{draft, rename, title, wait_until};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::Duration;
+use ticket_server::data::Ticket;
+use ticket_server::{launch, ClientError, ServerBuilder};
+
+#[test]
+fn shutdown_drains_queued_commands_and_flushes() {
+    let flushed: Arc<Mutex<Vec<Ticket>>> = Arc::default();
+    let sink = flushed.clone();
+    let server = ServerBuilder::new(100)
+        .persistence(move |tickets: &[Ticket]| {
+            sink.lock().unwrap().extend_from_slice(tickets);
+            Ok(())
+        })
+        .launch();
+
+    let writers: Vec<_> = (0..4)
+        .map(|_| {
+            let client = server.client();
+            spawn(move || {
+                for _ in 0..10 {
+                    client.insert(draft()).unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let client = server.client();
+    let report = server.shutdown().unwrap();
+    assert_eq!(report.commands_processed, 40);
+    assert!(report.flushed.unwrap().is_ok());
+    assert_eq!(flushed.lock().unwrap().len(), 40);
+
+    // Clients that outlive the server get an error, rather than hanging.
+    assert_eq!(client.insert(draft()), Err(ClientError::ShuttingDown));
+}
+
+#[test]
+fn shutdown_without_persistence() {
+    let server = launch(5);
+    server.insert(draft()).unwrap();
+    let report = server.shutdown().unwrap();
+    assert_eq!(report.commands_processed, 1);
+    assert!(report.flushed.is_none());
+}
+
+#[test]
+fn a_failed_flush_is_reported() {
+    let server = ServerBuilder::new(5)
+        .persistence(|_: &[Ticket]| Err(io::Error::other("disk full")))
+        .launch();
+    server.insert(draft()).unwrap();
+    let report = server.shutdown().unwrap();
+    assert_eq!(report.commands_processed, 1);
+    assert_eq!(
+        report.flushed.unwrap().unwrap_err().to_string(),
+        "disk full"
+    );
+}
+
+#[test]
+fn shutdown_gets_through_a_full_queue() {
+    let server = launch(1);
+    let id = server.insert(draft()).unwrap();
+
+    // Keep the server stuck on a patch, with one more command waiting in the queue.
+    let ticket = server.get(id).unwrap().unwrap();
+    let guard = ticket.lock().unwrap();
+    let stalled = server.client().with_timeout(Duration::from_millis(20));
+    assert!(stalled.update(rename(id, "A new title")).is_err());
+    wait_until(|| server.metrics().queue_depth == 0);
+    assert!(stalled.insert(draft()).is_err());
+    assert_eq!(server.insert(draft()), Err(ClientError::Overloaded));
+
+    let client = server.client();
+    let shutdown = spawn(move || server.shutdown());
+    wait_until(|| client.insert(draft()) != Err(ClientError::Overloaded));
+    // New commands are turned away right away, even before the queue drains.
+    assert_eq!(client.insert(draft()), Err(ClientError::ShuttingDown));
+
+    drop(guard);
+    let report = shutdown.join().unwrap().unwrap();
+    assert_eq!(report.commands_processed, 4);
+    assert_eq!(ticket.lock().unwrap().title, title("A new title"));
+}
+
+#[test]
+fn shutting_down_a_crashed_server_returns_its_panic() {
+    let server = launch(5);
+    let id = server.insert(draft()).unwrap();
+    let ticket = server.get(id).unwrap().unwrap();
+    let _ = spawn(move || {
+        let _guard = ticket.lock().unwrap();
+        panic!("Poisoning the lock");
+    })
+    .join();
+    // Listing the tickets reads every one of them: the server panics on the poisoned lock.
+    assert_eq!(server.list(), Err(ClientError::RequestDropped));
+    assert!(server.shutdown().is_err());
+}
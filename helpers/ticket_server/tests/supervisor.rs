@@ -0,0 +1,103 @@
+mod common;
+use common::{draft, rename};
+use std::thread::spawn;
+use ticket_fields::test_helpers::ticket_title;
+use ticket_server::store::TicketId;
+use ticket_server::{ClientError, ServerBuilder};
+
+// The server will panic the next time it tries to lock this ticket.
+fn poison(client: &ticket_server::TicketStoreClient, id: TicketId) {
+    let ticket = client.get(id).unwrap().unwrap();
+    let _ = spawn(move || {
+        let _guard = ticket.lock().unwrap();
+        panic!("Poisoning the lock");
+    })
+    .join();
+}
+
+#[test]
+fn a_supervised_server_restarts_with_its_state() {
+    let server = ServerBuilder::new(5).supervised(2).launch();
+    // Five mutations: two checkpoints, plus one command in the journal.
+    let ids: Vec<_> = (0..4).map(|_| server.insert(draft()).unwrap()).collect();
+    server.update(rename(ids[0], "Renamed")).unwrap();
+
+    poison(&server, ids[1]);
+    assert_eq!(
+        server.update(rename(ids[1], "Lost")),
+        Err(ClientError::RequestDropped)
+    );
+
+    // Same client, same ids: nothing acknowledged before the panic went missing.
+    for &id in &ids {
+        assert!(server.get(id).unwrap().is_some());
+    }
+    let status = server.supervisor_status();
+    assert_eq!(status.restarts, 1);
+    assert!(status.last_panic.unwrap().contains("PoisonError"));
+    let renamed = server.get(ids[0]).unwrap().unwrap();
+    assert_eq!(renamed.lock().unwrap().title, "Renamed".try_into().unwrap());
+    // The patch that crashed the server was not applied.
+    let crashed = server.get(ids[1]).unwrap().unwrap();
+    assert_eq!(crashed.lock().unwrap().title, ticket_title());
+
+    // Ids keep going from where they were.
+    let id = server.insert(draft()).unwrap();
+    assert!(!ids.contains(&id));
+    server.update(rename(ids[1], "Recovered")).unwrap();
+}
+
+#[test]
+fn an_unsupervised_server_is_not_restarted() {
+    let server = ServerBuilder::new(5).launch();
+    let id = server.insert(draft()).unwrap();
+    poison(&server, id);
+    assert_eq!(
+        server.update(rename(id, "Lost")),
+        Err(ClientError::RequestDropped)
+    );
+    assert!(server.shutdown().is_err());
+}
+
+#[test]
+fn handle_edits_survive_a_restart() {
+    let server = ServerBuilder::new(5).supervised(3).launch();
+    let ids: Vec<_> = (0..3).map(|_| server.insert(draft()).unwrap()).collect();
+    let handles: Vec<_> = ids
+        .iter()
+        .map(|id| server.get(*id).unwrap().unwrap())
+        .collect();
+
+    // The server journals this edit along with the next command...
+    handles[0].lock().unwrap().title = "Journaled".try_into().unwrap();
+    let inserted = server.insert(draft()).unwrap();
+    poison(&server, ids[2]);
+    // ...and this one when it restarts. Made after poisoning, so that journaling it
+    // doesn't trigger a checkpoint while the ticket is being poisoned.
+    handles[1].lock().unwrap().title = "Pending".try_into().unwrap();
+    assert_eq!(
+        server.update(rename(ids[2], "Lost")),
+        Err(ClientError::RequestDropped)
+    );
+    server.get(inserted).unwrap().unwrap();
+    assert_eq!(server.supervisor_status().restarts, 1);
+
+    for (id, title) in [(ids[0], "Journaled"), (ids[1], "Pending")] {
+        let ticket = server.get(id).unwrap().unwrap();
+        assert_eq!(ticket.lock().unwrap().title, title.try_into().unwrap());
+    }
+    // Handles obtained before the restart still point to the tickets in the store.
+    server.update(rename(ids[0], "Patched")).unwrap();
+    assert_eq!(
+        handles[0].lock().unwrap().title,
+        "Patched".try_into().unwrap()
+    );
+    handles[1].lock().unwrap().title = "Edited again".try_into().unwrap();
+    let ticket = server.get(ids[1]).unwrap().unwrap();
+    assert_eq!(
+        ticket.lock().unwrap().title,
+        "Edited again".try_into().unwrap()
+    );
+    // Including the one the server crashed on: it's back to its last known state.
+    assert_eq!(handles[2].lock().unwrap().title, ticket_title());
+}
@@ -0,0 +1,112 @@
+use std::time::{Duration, SystemTime};
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_server::clock::ManualClock;
+use ticket_server::data::{Status, StatusChange, TicketDraft, TicketPatch};
+use ticket_server::launch_with_clock;
+
+#[test]
+fn store_operations_are_timestamped() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let clock = ManualClock::new(start);
+    let client = launch_with_clock(5, clock.clone());
+
+    let draft = TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    };
+    let ticket_id = client.insert(draft).unwrap();
+    {
+        let ticket = client.get(ticket_id).unwrap().unwrap();
+        let ticket = ticket.lock().unwrap();
+        assert_eq!(ticket.created_at, start);
+        assert_eq!(ticket.updated_at, start);
+        assert!(ticket.status_changes.is_empty());
+    }
+
+    clock.advance(Duration::from_secs(60));
+    client
+        .update(TicketPatch {
+            title: Some("A new title".try_into().unwrap()),
+            ..TicketPatch::new(ticket_id)
+        })
+        .unwrap();
+
+    clock.advance(Duration::from_secs(60));
+    client
+        .update(TicketPatch {
+            status: Some(Status::InProgress),
+            ..TicketPatch::new(ticket_id)
+        })
+        .unwrap();
+
+    let ticket = client.get(ticket_id).unwrap().unwrap();
+    let ticket = ticket.lock().unwrap();
+    assert_eq!(ticket.created_at, start);
+    assert_eq!(ticket.updated_at, start + Duration::from_secs(120));
+    assert_eq!(
+        ticket.status_changes,
+        vec![StatusChange {
+            from: Status::ToDo,
+            to: Status::InProgress,
+            at: start + Duration::from_secs(120),
+        }]
+    );
+}
+
+#[test]
+fn no_op_patches_leave_updated_at_alone() {
+    let clock = ManualClock::default();
+    let client = launch_with_clock(5, clock.clone());
+    let ticket_id = client
+        .insert(TicketDraft {
+            title: ticket_title(),
+            description: ticket_description(),
+        })
+        .unwrap();
+
+    clock.advance(Duration::from_secs(60));
+    client
+        .update(TicketPatch {
+            title: Some(ticket_title()),
+            status: Some(Status::ToDo),
+            ..TicketPatch::new(ticket_id)
+        })
+        .unwrap();
+
+    let ticket = client.get(ticket_id).unwrap().unwrap();
+    let ticket = ticket.lock().unwrap();
+    assert_eq!(ticket.updated_at, SystemTime::UNIX_EPOCH);
+    assert!(ticket.status_changes.is_empty());
+}
+
+#[test]
+fn rejected_patches_and_handle_edits_are_not_timestamped() {
+    let clock = ManualClock::default();
+    let client = launch_with_clock(5, clock.clone());
+    let ticket_id = client
+        .insert(TicketDraft {
+            title: ticket_title(),
+            description: ticket_description(),
+        })
+        .unwrap();
+    let lease = client
+        .checkout(ticket_id, "alice".into(), Duration::from_secs(600))
+        .unwrap();
+
+    clock.advance(Duration::from_secs(60));
+    let rejected = client.update(TicketPatch {
+        status: Some(Status::Done),
+        ..TicketPatch::new(ticket_id)
+    });
+    assert!(rejected.is_err());
+    client.abandon(lease).unwrap();
+
+    // Handles bypass the store: the ticket changes, its timestamps don't.
+    let ticket = client.get(ticket_id).unwrap().unwrap();
+    ticket.lock().unwrap().status = Status::InProgress;
+
+    let ticket = ticket.lock().unwrap();
+    assert_eq!(ticket.status, Status::InProgress);
+    assert_eq!(ticket.updated_at, SystemTime::UNIX_EPOCH);
+    assert!(ticket.status_changes.is_empty());
+}
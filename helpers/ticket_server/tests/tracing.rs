@@ -0,0 +1,165 @@
+mod common;
+use common::draft;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use tracing::dispatcher::{with_default, Dispatch};
+use tracing_subscriber::layer::SubscriberExt;
+
+use ticket_server::data::TicketPatch;
+use ticket_server::trace::SpanRecorder;
+use ticket_server::{ClientError, ServerBuilder};
+
+// Records into `recorder`, on top of a registry keeping track of the spans themselves.
+fn recording(recorder: &SpanRecorder) -> Dispatch {
+    Dispatch::new(tracing_subscriber::registry().with(recorder.clone()))
+}
+
+#[test]
+fn server_spans_follow_from_client_spans() {
+    let recorder = SpanRecorder::new();
+    with_default(&recording(&recorder), || {
+        let server = ServerBuilder::new(5).launch();
+        let id = server.insert(draft()).unwrap();
+        server.get(id).unwrap().unwrap();
+        // Wait for the server to be done recording.
+        server.shutdown().unwrap();
+    });
+
+    let requests = recorder.spans_named("request");
+    let processed = recorder.spans_named("process");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(processed.len(), 2);
+    for (request, process) in requests.iter().zip(&processed) {
+        assert_eq!(process.follows_from, vec![request.id]);
+        assert_eq!(process.parent, None);
+        assert_eq!(process.field("request_id"), request.field("request_id"));
+        assert_eq!(process.field("command"), request.field("command"));
+        assert!(process.field("queue_wait_us").is_some());
+        assert!(process.field("handling_us").is_some());
+    }
+    assert_eq!(requests[0].field("command"), Some("insert"));
+    assert_eq!(requests[1].field("command"), Some("get"));
+}
+
+#[test]
+fn every_request_gets_its_own_correlation_id() {
+    let recorder = SpanRecorder::new();
+    let dispatch = recording(&recorder);
+    with_default(&dispatch, || {
+        let server = ServerBuilder::new(5).launch();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let client = server.client();
+                let dispatch = &dispatch;
+                scope.spawn(move || {
+                    with_default(dispatch, || {
+                        for _ in 0..10 {
+                            client.insert(draft()).unwrap();
+                        }
+                    })
+                });
+            }
+        });
+        server.shutdown().unwrap();
+    });
+
+    let ids = |name| {
+        recorder
+            .spans_named(name)
+            .iter()
+            .map(|span| span.field("request_id").unwrap().to_owned())
+            .collect::<BTreeSet<_>>()
+    };
+    let requests = ids("request");
+    assert_eq!(requests.len(), 40);
+    assert_eq!(ids("process"), requests);
+}
+
+#[test]
+fn fan_outs_trace_each_worker_request() {
+    let recorder = SpanRecorder::new();
+    with_default(&recording(&recorder), || {
+        let server = ServerBuilder::new(5).launch_partitioned(3);
+        // New tickets are handed to workers in turn: one each.
+        let ids: Vec<_> = (0..3).map(|_| server.insert(draft()).unwrap()).collect();
+        server.get_many(ids).unwrap();
+        server.shutdown().unwrap();
+    });
+
+    let requests: Vec<_> = recorder
+        .spans_named("request")
+        .into_iter()
+        .filter(|span| span.field("command") == Some("get_many"))
+        .collect();
+    let processed: Vec<_> = recorder
+        .spans_named("process")
+        .into_iter()
+        .filter(|span| span.field("command") == Some("get_many"))
+        .collect();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(processed.len(), 3);
+    let ids: BTreeSet<_> = requests
+        .iter()
+        .map(|span| span.field("request_id").unwrap())
+        .collect();
+    assert_eq!(ids.len(), 3);
+    for process in &processed {
+        let request = requests
+            .iter()
+            .find(|request| process.follows_from == vec![request.id])
+            .unwrap();
+        assert_eq!(process.field("request_id"), request.field("request_id"));
+    }
+}
+
+#[test]
+fn only_the_latest_closed_spans_are_kept() {
+    let recorder = SpanRecorder::with_capacity(4);
+    with_default(&recording(&recorder), || {
+        let server = ServerBuilder::new(5).launch();
+        for _ in 0..10 {
+            server.insert(draft()).unwrap();
+        }
+        server.shutdown().unwrap();
+    });
+
+    let spans = recorder.spans();
+    assert_eq!(spans.len(), 4);
+    // The last two inserts, on both sides.
+    let mut ids: Vec<u64> = spans
+        .iter()
+        .map(|span| span.field("request_id").unwrap().parse().unwrap())
+        .collect();
+    ids.sort();
+    assert_eq!(ids[0], ids[1]);
+    assert_eq!(ids[2], ids[3]);
+    assert!(ids[1] < ids[2]);
+}
+
+#[test]
+fn requests_the_client_gave_up_on_are_still_linked() {
+    let recorder = SpanRecorder::new();
+    with_default(&recording(&recorder), || {
+        let server = ServerBuilder::new(5).launch();
+        let client = server.client().with_timeout(Duration::from_millis(10));
+        let id = client.insert(draft()).unwrap();
+        // Keep the ticket locked, so that the server gets stuck applying the patch.
+        let ticket = client.get(id).unwrap().unwrap();
+        let guard = ticket.lock().unwrap();
+        let patch = TicketPatch::new(id);
+        assert!(matches!(
+            client.update(patch),
+            Err(ClientError::TimedOut(_))
+        ));
+        drop(guard);
+        server.shutdown().unwrap();
+    });
+
+    let request = recorder.spans_named("request").pop().unwrap();
+    let process = recorder.spans_named("process").pop().unwrap();
+    assert_eq!(request.field("command"), Some("update"));
+    assert_eq!(process.follows_from, vec![request.id]);
+    assert_eq!(process.field("request_id"), request.field("request_id"));
+    assert!(process.field("handling_us").is_some());
+}